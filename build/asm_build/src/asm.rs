@@ -0,0 +1,1052 @@
+//! Assembler for the subset of `bass` syntax used by the randomizer's
+//! patches.
+//!
+//! Supported:
+//! * `arch pce.cpu`
+//! * `origin` and `base`
+//! * `macro name(variable a, ...) { ... }` and macro invocation
+//! * `db` and `dw`
+//! * labels
+//! * HuC6280 instructions (see [`neutopia::huc6280`])
+//! * `//` comments and `;` statement separators
+//! * `slot name, size`: a non-`bass` extension that defines label `name`
+//!   and reserves `size` zero bytes for the randomizer to fill in.
+//! * `bank bank, address`: a non-`bass` extension that sets `origin` to
+//!   the file offset of `address` with `bank` mapped into its page and
+//!   `base` to `address` (see [`neutopia::bank`]).
+//!
+//! Like `bass`, `origin` sets the file offset that is written to and `base`
+//! sets the address the CPU sees at that offset.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use std::convert::TryFrom;
+
+use failure::{format_err, Error};
+use neutopia::{
+    bank::Address,
+    huc6280::{self, Mode},
+    ips::Hunk,
+};
+use serde::Serialize;
+
+/// Location of a label.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Symbol {
+    /// Address the CPU sees (set by `base`).
+    pub address: u32,
+    /// File offset (set by `origin`).
+    pub offset: u32,
+    /// Number of bytes reserved if the label is a `slot`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u32>,
+}
+
+/// Output of assembling a source file.
+#[derive(Debug)]
+pub struct Assembly {
+    /// Bytes written, sorted by file offset.
+    pub hunks: Vec<Hunk>,
+    /// Labels by name.
+    pub symbols: BTreeMap<String, Symbol>,
+}
+
+#[derive(Clone, Debug)]
+struct Statement {
+    line: usize,
+    text: String,
+}
+
+#[derive(Debug)]
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Statement>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Pass {
+    // Collects label addresses.
+    Layout,
+    // Evaluates operands and emits bytes.
+    Emit,
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(is_ident_start) && chars.all(is_ident_char)
+}
+
+// Split `s` on commas that are not inside parentheses.
+fn split_args(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut cur = String::new();
+    for c in s.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(cur.trim().to_string());
+                cur.clear();
+                continue;
+            }
+            _ => (),
+        }
+        cur.push(c);
+    }
+    args.push(cur.trim().to_string());
+    args
+}
+
+// Returns the inside of `s` if it is entirely wrapped in one pair of
+// parentheses.
+fn strip_parens(s: &str) -> Option<&str> {
+    if !s.starts_with('(') {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return if i == s.len() - 1 {
+                        Some(&s[1..i])
+                    } else {
+                        None
+                    };
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+// Split source into statements, collecting macro definitions.
+fn parse_source(source: &str) -> Result<(Vec<Statement>, HashMap<String, Macro>), Error> {
+    let mut statements = Vec::new();
+    let mut macros = HashMap::new();
+    let mut cur_macro: Option<(String, Macro)> = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_num = i + 1;
+        let line = match line.find("//") {
+            Some(pos) => &line[..pos],
+            None => line,
+        };
+
+        for text in line.split(';') {
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            if text == "}" {
+                let (name, m) = cur_macro
+                    .take()
+                    .ok_or_else(|| format_err!("{}: unexpected }}", line_num))?;
+                macros.insert(name, m);
+                continue;
+            }
+
+            if let Some(header) = text.strip_prefix("macro ") {
+                if cur_macro.is_some() {
+                    return Err(format_err!("{}: nested macros are not supported", line_num));
+                }
+                cur_macro = Some(parse_macro_header(header, line_num)?);
+                continue;
+            }
+
+            let statement = Statement {
+                line: line_num,
+                text: text.to_string(),
+            };
+            match &mut cur_macro {
+                Some((_, m)) => m.body.push(statement),
+                None => statements.push(statement),
+            }
+        }
+    }
+
+    if let Some((name, _)) = cur_macro {
+        return Err(format_err!(
+            "{}: macro {} is missing a closing }}",
+            source.lines().count(),
+            name
+        ));
+    }
+
+    Ok((statements, macros))
+}
+
+fn parse_macro_header(header: &str, line: usize) -> Result<(String, Macro), Error> {
+    let header = header
+        .trim()
+        .strip_suffix('{')
+        .ok_or_else(|| format_err!("{}: expected {{ after macro declaration", line))?
+        .trim();
+    let open = header
+        .find('(')
+        .ok_or_else(|| format_err!("{}: expected ( in macro declaration", line))?;
+    let name = header[..open].trim();
+    let params = header[open..]
+        .strip_prefix('(')
+        .and_then(|p| p.strip_suffix(')'))
+        .ok_or_else(|| format_err!("{}: malformed macro parameters", line))?;
+    if !is_ident(name) {
+        return Err(format_err!("{}: invalid macro name {}", line, name));
+    }
+
+    let mut names = Vec::new();
+    for param in params
+        .split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+    {
+        let param = param.strip_prefix("variable ").unwrap_or(param).trim();
+        if !is_ident(param) {
+            return Err(format_err!("{}: invalid macro parameter {}", line, param));
+        }
+        names.push(param.to_string());
+    }
+
+    Ok((
+        name.to_string(),
+        Macro {
+            params: names,
+            body: Vec::new(),
+        },
+    ))
+}
+
+struct ExprParser<'a, 'b> {
+    asm: &'b Assembler<'a>,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<'a, 'b> ExprParser<'a, 'b> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        self.peek();
+        let matches = s
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+        if matches {
+            self.pos += s.len();
+        }
+        matches
+    }
+
+    fn expr(&mut self) -> Result<i64, Error> {
+        let mut value = self.and()?;
+        while self.eat("|") {
+            value |= self.and()?;
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<i64, Error> {
+        let mut value = self.shift()?;
+        while self.eat("&") {
+            value &= self.shift()?;
+        }
+        Ok(value)
+    }
+
+    fn shift(&mut self) -> Result<i64, Error> {
+        let mut value = self.sum()?;
+        loop {
+            if self.eat("<<") {
+                value = value.wrapping_shl(self.sum()? as u32);
+            } else if self.eat(">>") {
+                value = value.wrapping_shr(self.sum()? as u32);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn sum(&mut self) -> Result<i64, Error> {
+        let mut value = self.product()?;
+        loop {
+            if self.eat("+") {
+                value = value.wrapping_add(self.product()?);
+            } else if self.eat("-") {
+                value = value.wrapping_sub(self.product()?);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<i64, Error> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat("*") {
+                value = value.wrapping_mul(self.unary()?);
+            } else if self.eat("/") {
+                let divisor = self.unary()?;
+                if divisor == 0 {
+                    return Err(format_err!("division by zero"));
+                }
+                value = value.wrapping_div(divisor);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<i64, Error> {
+        if self.eat("-") {
+            Ok(self.unary()?.wrapping_neg())
+        } else {
+            self.primary()
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| f(*c)) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn number(&mut self, digits: &str, radix: u32) -> Result<i64, Error> {
+        i64::from_str_radix(digits, radix)
+            .map_err(|e| format_err!("invalid number {}: {}", digits, e))
+    }
+
+    fn primary(&mut self) -> Result<i64, Error> {
+        let c = self
+            .peek()
+            .ok_or_else(|| format_err!("unexpected end of expression"))?;
+        if self.eat("(") {
+            let value = self.expr()?;
+            if !self.eat(")") {
+                return Err(format_err!("expected )"));
+            }
+            Ok(value)
+        } else if self.eat("$") {
+            let digits = self.take_while(|c| c.is_ascii_hexdigit());
+            self.number(&digits, 16)
+        } else if self.eat("%") {
+            let digits = self.take_while(|c| c == '0' || c == '1');
+            self.number(&digits, 2)
+        } else if self.eat("0x") {
+            let digits = self.take_while(|c| c.is_ascii_hexdigit());
+            self.number(&digits, 16)
+        } else if c.is_ascii_digit() {
+            let digits = self.take_while(|c| c.is_ascii_digit());
+            self.number(&digits, 10)
+        } else if is_ident_start(c) {
+            let name = self.take_while(is_ident_char);
+            self.asm.lookup(&name)
+        } else {
+            Err(format_err!("unexpected {}", c))
+        }
+    }
+}
+
+// Returns true if `expr` is a number literal that fits in 8 bits as
+// written.  These select zero page addressing when both zero page and
+// absolute modes are available.
+fn is_short_literal(expr: &str) -> bool {
+    let expr = expr.trim();
+    if let Some(hex) = expr.strip_prefix('$') {
+        !hex.is_empty() && hex.len() <= 2 && hex.chars().all(|c| c.is_ascii_hexdigit())
+    } else {
+        expr.parse::<u8>().is_ok()
+    }
+}
+
+// Returns the candidate addressing modes for an operand, in order of
+// preference, along with the operand's expressions.
+fn operand_modes(operand: &str, size: Option<char>) -> Result<(Vec<Mode>, Vec<String>), Error> {
+    let sized = |zp: Mode, abs: Mode, expr: &str| match size {
+        Some('b') => vec![zp],
+        Some('w') => vec![abs],
+        _ if is_short_literal(expr) => vec![zp, abs],
+        _ => vec![abs, zp],
+    };
+
+    let operand = operand.trim();
+    if operand.is_empty() {
+        return Ok((vec![Mode::Implied], Vec::new()));
+    }
+
+    let parts = split_args(operand);
+    let index = parts.last().map(|p| p.to_ascii_lowercase());
+    let index = index.as_deref();
+    let bad_operand = || format_err!("invalid operand {}", operand);
+
+    match (parts.len(), index) {
+        (1, _) => {
+            let p = &parts[0];
+            if let Some(imm) = p.strip_prefix('#') {
+                Ok((vec![Mode::Immediate], vec![imm.to_string()]))
+            } else if let Some(inner) = strip_parens(p) {
+                let inner = split_args(inner);
+                match inner.len() {
+                    1 => Ok((vec![Mode::ZeroPageIndirect, Mode::AbsoluteIndirect], inner)),
+                    2 if inner[1].eq_ignore_ascii_case("x") => Ok((
+                        vec![Mode::ZeroPageIndirectX, Mode::AbsoluteIndirectX],
+                        vec![inner[0].clone()],
+                    )),
+                    _ => Err(bad_operand()),
+                }
+            } else {
+                let mut modes = vec![Mode::Relative];
+                modes.append(&mut sized(Mode::ZeroPage, Mode::Absolute, p));
+                Ok((modes, vec![p.clone()]))
+            }
+        }
+        (2, Some("y")) if strip_parens(&parts[0]).is_some() => Ok((
+            vec![Mode::ZeroPageIndirectY],
+            vec![strip_parens(&parts[0]).unwrap().to_string()],
+        )),
+        (2, Some("x")) => Ok((
+            sized(Mode::ZeroPageX, Mode::AbsoluteX, &parts[0]),
+            vec![parts[0].clone()],
+        )),
+        (2, Some("y")) => Ok((
+            sized(Mode::ZeroPageY, Mode::AbsoluteY, &parts[0]),
+            vec![parts[0].clone()],
+        )),
+        (2, _) => match parts[0].strip_prefix('#') {
+            Some(imm) => Ok((
+                sized(Mode::ImmediateZeroPage, Mode::ImmediateAbsolute, &parts[1]),
+                vec![imm.to_string(), parts[1].clone()],
+            )),
+            None => Ok((vec![Mode::ZeroPageRelative], parts)),
+        },
+        (3, Some("x")) => match parts[0].strip_prefix('#') {
+            Some(imm) => Ok((
+                sized(
+                    Mode::ImmediateZeroPageX,
+                    Mode::ImmediateAbsoluteX,
+                    &parts[1],
+                ),
+                vec![imm.to_string(), parts[1].clone()],
+            )),
+            None => Err(bad_operand()),
+        },
+        (3, _) => Ok((vec![Mode::Block], parts)),
+        _ => Err(bad_operand()),
+    }
+}
+
+fn check_range(value: i64, min: i64, max: i64, what: &str) -> Result<i64, Error> {
+    if value < min || value > max {
+        return Err(format_err!("{} {:#x} out of range", what, value));
+    }
+    Ok(value)
+}
+
+struct Assembler<'a> {
+    macros: &'a HashMap<String, Macro>,
+    pass: Pass,
+    arch: bool,
+    origin: i64,
+    base_offset: i64,
+    labels: HashMap<String, i64>,
+    offsets: HashMap<String, i64>,
+    slots: HashMap<String, u32>,
+    scopes: Vec<HashMap<String, i64>>,
+    output: BTreeMap<u32, u8>,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(macros: &'a HashMap<String, Macro>, labels: HashMap<String, i64>, pass: Pass) -> Self {
+        Self {
+            macros,
+            pass,
+            arch: false,
+            origin: 0,
+            base_offset: 0,
+            labels,
+            offsets: HashMap::new(),
+            slots: HashMap::new(),
+            scopes: Vec::new(),
+            output: BTreeMap::new(),
+        }
+    }
+
+    fn pc(&self) -> i64 {
+        self.origin + self.base_offset
+    }
+
+    fn lookup(&self, name: &str) -> Result<i64, Error> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.labels.get(name))
+            .copied()
+            .ok_or_else(|| format_err!("undefined symbol {}", name))
+    }
+
+    fn eval(&self, expr: &str) -> Result<i64, Error> {
+        let mut parser = ExprParser {
+            asm: self,
+            chars: expr.chars().collect(),
+            pos: 0,
+        };
+        let value = parser.expr()?;
+        if parser.peek().is_some() {
+            return Err(format_err!("unexpected trailing characters in {}", expr));
+        }
+        Ok(value)
+    }
+
+    // Operands may refer to labels defined later so are only evaluated
+    // once all labels are known.
+    fn eval_operand(&self, expr: &str) -> Result<i64, Error> {
+        match self.pass {
+            Pass::Layout => Ok(0),
+            Pass::Emit => self.eval(expr),
+        }
+    }
+
+    fn emit(&mut self, data: &[u8]) -> Result<(), Error> {
+        if self.origin < 0 || self.origin + data.len() as i64 > u32::MAX as i64 {
+            return Err(format_err!("origin {:#x} out of range", self.origin));
+        }
+        if self.pass == Pass::Emit {
+            for (i, b) in data.iter().enumerate() {
+                self.output.insert(self.origin as u32 + i as u32, *b);
+            }
+        }
+        self.origin += data.len() as i64;
+        Ok(())
+    }
+
+    fn define_label(&mut self, name: &str) -> Result<(), Error> {
+        if !is_ident(name) {
+            return Err(format_err!("invalid label {}", name));
+        }
+        let pc = self.pc();
+        if self.pass == Pass::Layout {
+            if self.labels.insert(name.to_string(), pc).is_some() {
+                return Err(format_err!("label {} redefined", name));
+            }
+            self.offsets.insert(name.to_string(), self.origin);
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, statements: &[Statement]) -> Result<(), Error> {
+        for statement in statements {
+            self.statement(&statement.text)
+                .map_err(|e| format_err!("{}: {}", statement.line, e))?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, text: &str) -> Result<(), Error> {
+        if let Some(colon) = text.find(':') {
+            let name = text[..colon].trim();
+            if is_ident(name) {
+                self.define_label(name)?;
+                let rest = text[colon + 1..].trim();
+                return if rest.is_empty() {
+                    Ok(())
+                } else {
+                    self.statement(rest)
+                };
+            }
+        }
+
+        let end = text
+            .find(|c: char| c.is_whitespace() || c == '(')
+            .unwrap_or(text.len());
+        let (keyword, rest) = (&text[..end], text[end..].trim());
+
+        match keyword {
+            "arch" => {
+                if rest != "pce.cpu" {
+                    return Err(format_err!("unsupported architecture {}", rest));
+                }
+                self.arch = true;
+                Ok(())
+            }
+            "origin" => {
+                self.origin = self.eval(rest)?;
+                Ok(())
+            }
+            "base" => {
+                self.base_offset = self.eval(rest)? - self.origin;
+                Ok(())
+            }
+            "db" => {
+                for arg in split_args(rest) {
+                    let value = check_range(self.eval_operand(&arg)?, -0x80, 0xff, "byte")?;
+                    self.emit(&[value as u8])?;
+                }
+                Ok(())
+            }
+            "dw" => {
+                for arg in split_args(rest) {
+                    let value = check_range(self.eval_operand(&arg)?, -0x8000, 0xffff, "word")?;
+                    self.emit(&(value as u16).to_le_bytes())?;
+                }
+                Ok(())
+            }
+            "slot" => self.slot(rest),
+            "bank" => self.bank(rest),
+            _ if self.macros.contains_key(keyword) => self.invoke(keyword, rest),
+            _ => self.instruction(keyword, rest),
+        }
+    }
+
+    fn slot(&mut self, args: &str) -> Result<(), Error> {
+        let args = split_args(args);
+        if args.len() != 2 {
+            return Err(format_err!("slot takes a name and a size"));
+        }
+        let size = check_range(self.eval(&args[1])?, 1, 0x10000, "slot size")? as u32;
+
+        self.define_label(&args[0])?;
+        if self.pass == Pass::Layout {
+            self.slots.insert(args[0].clone(), size);
+        }
+        self.emit(&vec![0; size as usize])
+    }
+
+    fn bank(&mut self, args: &str) -> Result<(), Error> {
+        let args = split_args(args);
+        if args.len() != 2 {
+            return Err(format_err!("bank takes a bank and an address"));
+        }
+        let bank = check_range(self.eval(&args[0])?, 0, 0xff, "bank")? as u8;
+        let address = check_range(self.eval(&args[1])?, 0, 0xffff, "address")?;
+
+        self.origin = Address::new(bank, address as u16).offset()? as i64;
+        self.base_offset = address - self.origin;
+        Ok(())
+    }
+
+    fn invoke(&mut self, name: &str, args: &str) -> Result<(), Error> {
+        let args = args
+            .strip_prefix('(')
+            .and_then(|a| a.strip_suffix(')'))
+            .ok_or_else(|| format_err!("expected arguments to macro {}", name))?;
+        let args: Vec<String> = split_args(args)
+            .into_iter()
+            .filter(|a| !a.is_empty())
+            .collect();
+
+        let m = &self.macros[name];
+        if args.len() != m.params.len() {
+            return Err(format_err!(
+                "macro {} takes {} arguments, {} given",
+                name,
+                m.params.len(),
+                args.len()
+            ));
+        }
+
+        let mut scope = HashMap::new();
+        for (param, arg) in m.params.iter().zip(args.iter()) {
+            scope.insert(param.clone(), self.eval(arg)?);
+        }
+
+        self.scopes.push(scope);
+        let result = self
+            .run(&m.body)
+            .map_err(|e| format_err!("in macro {}: {}", name, e));
+        self.scopes.pop();
+        result
+    }
+
+    fn instruction(&mut self, mnemonic: &str, operand: &str) -> Result<(), Error> {
+        let (mnemonic, size) = match mnemonic.rfind('.') {
+            Some(dot) => {
+                let size = mnemonic[dot + 1..].to_ascii_lowercase();
+                match size.as_str() {
+                    "b" => (&mnemonic[..dot], Some('b')),
+                    "w" => (&mnemonic[..dot], Some('w')),
+                    _ => return Err(format_err!("unknown size suffix .{}", size)),
+                }
+            }
+            None => (mnemonic, None),
+        };
+
+        if !huc6280::is_mnemonic(mnemonic) {
+            return Err(format_err!("unknown directive or instruction {}", mnemonic));
+        }
+        if !self.arch {
+            return Err(format_err!("instruction {} before arch", mnemonic));
+        }
+
+        let (modes, exprs) = operand_modes(operand, size)?;
+        let op = modes
+            .iter()
+            .find_map(|mode| huc6280::encode(mnemonic, *mode))
+            .ok_or_else(|| format_err!("{} does not support operand \"{}\"", mnemonic, operand))?;
+
+        let pc = self.pc();
+        let values = exprs
+            .iter()
+            .map(|e| self.eval_operand(e))
+            .collect::<Result<Vec<i64>, Error>>()?;
+        let byte = |v: i64| check_range(v, -0x80, 0xff, "immediate").map(|v| vec![v as u8]);
+        let zp = |v: i64| check_range(v, 0, 0xff, "zero page address").map(|v| vec![v as u8]);
+        let word = |v: i64| {
+            check_range(v, 0, 0xffff, "address").map(|v| (v as u16).to_le_bytes().to_vec())
+        };
+        let rel = |target: i64, next: i64| {
+            if self.pass == Pass::Layout {
+                return Ok(vec![0]);
+            }
+            check_range(target - next, -0x80, 0x7f, "branch offset").map(|v| vec![v as u8])
+        };
+
+        let operand_bytes: Vec<Vec<u8>> = match op.mode {
+            Mode::Implied => Vec::new(),
+            Mode::Immediate => vec![byte(values[0])?],
+            Mode::ZeroPage
+            | Mode::ZeroPageX
+            | Mode::ZeroPageY
+            | Mode::ZeroPageIndirect
+            | Mode::ZeroPageIndirectX
+            | Mode::ZeroPageIndirectY => vec![zp(values[0])?],
+            Mode::Absolute
+            | Mode::AbsoluteX
+            | Mode::AbsoluteY
+            | Mode::AbsoluteIndirect
+            | Mode::AbsoluteIndirectX => vec![word(values[0])?],
+            Mode::Relative => vec![rel(values[0], pc + 2)?],
+            Mode::ZeroPageRelative => vec![zp(values[0])?, rel(values[1], pc + 3)?],
+            Mode::ImmediateZeroPage | Mode::ImmediateZeroPageX => {
+                vec![byte(values[0])?, zp(values[1])?]
+            }
+            Mode::ImmediateAbsolute | Mode::ImmediateAbsoluteX => {
+                vec![byte(values[0])?, word(values[1])?]
+            }
+            Mode::Block => vec![word(values[0])?, word(values[1])?, word(values[2])?],
+        };
+
+        let mut data = vec![op.code];
+        data.extend(operand_bytes.into_iter().flatten());
+        self.emit(&data)
+    }
+}
+
+// Coalesce written bytes into hunks of consecutive offsets.
+fn hunks(output: &BTreeMap<u32, u8>) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for (offset, b) in output {
+        match hunks.last_mut() {
+            Some(hunk) if hunk.offset + hunk.data.len() as u32 == *offset => hunk.data.push(*b),
+            _ => hunks.push(Hunk {
+                offset: *offset,
+                data: vec![*b],
+            }),
+        }
+    }
+    hunks
+}
+
+/// Assemble `source`.  `name` is used in error messages.
+pub fn assemble(name: &str, source: &str) -> Result<Assembly, Error> {
+    let (statements, macros) = parse_source(source).map_err(|e| format_err!("{}:{}", name, e))?;
+
+    let mut layout = Assembler::new(&macros, HashMap::new(), Pass::Layout);
+    layout
+        .run(&statements)
+        .map_err(|e| format_err!("{}:{}", name, e))?;
+
+    let mut symbols = BTreeMap::new();
+    for (label, address) in &layout.labels {
+        let offset = layout.offsets[label];
+        let symbol = u32::try_from(*address)
+            .and_then(|address| Ok((address, u32::try_from(offset)?)))
+            .map(|(address, offset)| Symbol {
+                address,
+                offset,
+                size: layout.slots.get(label).copied(),
+            })
+            .map_err(|_| format_err!("{}: label {} out of range", name, label))?;
+        symbols.insert(label.clone(), symbol);
+    }
+
+    let mut emit = Assembler::new(&macros, layout.labels, Pass::Emit);
+    emit.run(&statements)
+        .map_err(|e| format_err!("{}:{}", name, e))?;
+
+    Ok(Assembly {
+        hunks: hunks(&emit.output),
+        symbols,
+    })
+}
+
+/// Assemble the file at `path`.
+pub fn assemble_file(path: &Path) -> Result<Assembly, Error> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format_err!("unable to read {}: {}", path.to_string_lossy(), e))?;
+    assemble(&path.to_string_lossy(), &source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(offset: u32, data: &[u8]) -> Hunk {
+        Hunk {
+            offset,
+            data: data.to_vec(),
+        }
+    }
+
+    fn asm(source: &str) -> Vec<Hunk> {
+        assemble("test.asm", source).unwrap().hunks
+    }
+
+    #[test]
+    fn test_expressions() {
+        assert_eq!(
+            asm("arch pce.cpu\norigin $10\ndb $12, 34, %101, (2+3)*4, $10-1, 1<<4 | 1\n"),
+            vec![hunk(0x10, &[0x12, 34, 5, 20, 0x0f, 0x11])]
+        );
+        assert_eq!(
+            asm("arch pce.cpu; origin $0; dw $1234, label; label:"),
+            vec![hunk(0, &[0x34, 0x12, 0x04, 0x00])]
+        );
+    }
+
+    #[test]
+    fn test_addressing_modes() {
+        let source = "
+            arch pce.cpu
+            origin $100
+            base $8000
+            lda #$12
+            lda $12
+            lda $1234
+            lda.w $12
+            lda $12,x
+            lda $1234,y
+            lda ($12)
+            lda ($12,x)
+            lda ($12),y
+            jmp ($1234)
+            jmp ($1234,x)
+            tii $1000, $2000, $0010
+            tst #$01, $12
+            tst #$01, $1234,x
+        ";
+        assert_eq!(
+            asm(source),
+            vec![hunk(
+                0x100,
+                &[
+                    0xa9, 0x12, // lda #$12
+                    0xa5, 0x12, // lda $12
+                    0xad, 0x34, 0x12, // lda $1234
+                    0xad, 0x12, 0x00, // lda.w $12
+                    0xb5, 0x12, // lda $12,x
+                    0xb9, 0x34, 0x12, // lda $1234,y
+                    0xb2, 0x12, // lda ($12)
+                    0xa1, 0x12, // lda ($12,x)
+                    0xb1, 0x12, // lda ($12),y
+                    0x6c, 0x34, 0x12, // jmp ($1234)
+                    0x7c, 0x34, 0x12, // jmp ($1234,x)
+                    0x73, 0x00, 0x10, 0x00, 0x20, 0x10, 0x00, // tii
+                    0x83, 0x01, 0x12, // tst #$01, $12
+                    0xb3, 0x01, 0x34, 0x12, // tst #$01, $1234,x
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_branches_and_labels() {
+        let source = "
+            arch pce.cpu
+            origin $0
+            base $8000
+        start:
+            bra forward
+            nop
+        forward:
+            bcc start
+            bbr0 $12, start
+        ";
+        let assembly = assemble("test.asm", source).unwrap();
+        assert_eq!(
+            assembly.hunks,
+            vec![hunk(0, &[0x80, 0x01, 0xea, 0x90, 0xfb, 0x0f, 0x12, 0xf8])]
+        );
+        assert_eq!(
+            assembly.symbols["start"],
+            Symbol {
+                address: 0x8000,
+                offset: 0,
+                size: None,
+            }
+        );
+        assert_eq!(
+            assembly.symbols["forward"],
+            Symbol {
+                address: 0x8003,
+                offset: 3,
+                size: None,
+            }
+        );
+
+        let err = assemble("test.asm", "arch pce.cpu\norigin 0\nbra $1000\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "test.asm:3: branch offset 0xffe out of range"
+        );
+    }
+
+    #[test]
+    fn test_slot() {
+        let source = "
+            arch pce.cpu
+            origin $100
+            base $c000
+            lda starting_bombs
+            slot starting_bombs, 1
+            slot flags, 2 * 2
+        ";
+        let assembly = assemble("test.asm", source).unwrap();
+        assert_eq!(
+            assembly.hunks,
+            vec![hunk(0x100, &[0xad, 0x03, 0xc0, 0, 0, 0, 0, 0])]
+        );
+        assert_eq!(
+            assembly.symbols["starting_bombs"],
+            Symbol {
+                address: 0xc003,
+                offset: 0x103,
+                size: Some(1),
+            }
+        );
+        assert_eq!(assembly.symbols["flags"].size, Some(4));
+
+        assert!(assemble("test.asm", "slot a, 0").is_err());
+        assert!(assemble("test.asm", "slot a").is_err());
+    }
+
+    // Matches the output of `bass` for rando/src/asm/no-downgrade.asm.
+    #[test]
+    fn test_macro_seek() {
+        let source = "
+            arch pce.cpu
+
+            macro seek(variable offset) {
+              origin (offset - $6000)
+              base offset
+            }
+
+            seek($ddf1);
+            get_sword_handler:
+                dw handle_upgradeable_item // comment
+                dw handle_upgradeable_item
+                dw handle_upgradeable_item
+
+            seek($df52);
+            handle_upgradeable_item:
+                ldx  $35d0
+                lda  $35d1
+                cmp $2e44,x
+                bcc skip_write
+                sta $2e44,x
+            skip_write:
+                lda  $2e44,x
+                sta  $35d1
+                jmp  $de79
+        ";
+        assert_eq!(
+            asm(source),
+            vec![
+                hunk(0x7df1, &[0x52, 0xdf, 0x52, 0xdf, 0x52, 0xdf]),
+                hunk(
+                    0x7f52,
+                    &[
+                        0xae, 0xd0, 0x35, 0xad, 0xd1, 0x35, 0xdd, 0x44, 0x2e, 0x90, 0x03, 0x9d,
+                        0x44, 0x2e, 0xbd, 0x44, 0x2e, 0x8d, 0xd1, 0x35, 0x4c, 0x79, 0xde
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bank() {
+        let source = "
+            arch pce.cpu
+            bank $03, $df52
+            handler:
+                jmp handler
+            bank $48, $454e
+                db $01
+        ";
+        let assembly = assemble("test.asm", source).unwrap();
+        assert_eq!(
+            assembly.hunks,
+            vec![hunk(0x7f52, &[0x4c, 0x52, 0xdf]), hunk(0x5054e, &[0x01])]
+        );
+        assert_eq!(assembly.symbols["handler"].offset, 0x7f52);
+
+        assert!(assemble("test.asm", "bank $80, $c000").is_err());
+        assert!(assemble("test.asm", "bank $03").is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |source: &str| assemble("test.asm", source).unwrap_err().to_string();
+        assert_eq!(
+            err("arch snes.cpu"),
+            "test.asm:1: unsupported architecture snes.cpu"
+        );
+        assert_eq!(
+            err("origin 0\nnop"),
+            "test.asm:2: instruction nop before arch"
+        );
+        assert_eq!(
+            err("arch pce.cpu\nfoo $12"),
+            "test.asm:2: unknown directive or instruction foo"
+        );
+        assert_eq!(
+            err("arch pce.cpu\nsta #$12"),
+            "test.asm:2: sta does not support operand \"#$12\""
+        );
+        assert_eq!(
+            err("arch pce.cpu\njmp missing"),
+            "test.asm:2: undefined symbol missing"
+        );
+        assert_eq!(err("a:\na:"), "test.asm:2: label a redefined");
+        assert_eq!(
+            err("macro m(variable a) {\norigin a\n}\nm(1, 2)"),
+            "test.asm:4: macro m takes 1 arguments, 2 given"
+        );
+        assert_eq!(
+            err("macro m() {\n"),
+            "test.asm:1: macro m is missing a closing }"
+        );
+    }
+}
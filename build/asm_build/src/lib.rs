@@ -1,117 +1,274 @@
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
-use byteorder::{BigEndian, WriteBytesExt};
 use failure::{format_err, Error};
+use neutopia::{
+    bps,
+    ips::{self, Hunk},
+};
 
-fn make_target_file<P: AsRef<Path>>(path: P, size: usize, value: u8) -> Result<(), Error> {
-    let data: Vec<u8> = vec![value; size];
-    let mut f = File::create(&path).map_err(|e| {
-        format_err!(
-            "unable to create temporary file {}: {}",
-            path.as_ref().to_string_lossy(),
-            e
-        )
-    })?;
-    f.write_all(&data)?;
+pub mod asm;
 
-    Ok(())
+pub use asm::Symbol;
+
+/// Labels from one or more assembled files by name.
+pub type SymbolTable = BTreeMap<String, Symbol>;
+
+fn read_file(path: &Path) -> Result<Vec<u8>, Error> {
+    let mut f = File::open(path)
+        .map_err(|e| format_err!("unable to open {}: {}", path.to_string_lossy(), e))?;
+    let mut data = Vec::new();
+    f.read_to_end(&mut data)?;
+    Ok(data)
 }
 
-fn run_bass(bass: &PathBuf, source: &PathBuf, out: &PathBuf) -> Result<(), Error> {
-    let out = Command::new(bass)
-        .args(&["-o", &out.to_string_lossy(), &source.to_string_lossy()])
-        .output()
-        .map_err(|e| format_err!("failed to execute {}: {}", bass.to_string_lossy(), e))?;
+/// Add the labels assembled from `file` to `symbols`.
+///
+/// Labels are global across files so defining one twice is an error.
+pub fn merge_symbols(
+    symbols: &mut SymbolTable,
+    new: SymbolTable,
+    file: &Path,
+) -> Result<(), Error> {
+    for (name, symbol) in new {
+        if symbols.insert(name.clone(), symbol).is_some() {
+            return Err(format_err!(
+                "label {} in {} is already defined",
+                name,
+                file.to_string_lossy()
+            ));
+        }
+    }
+    Ok(())
+}
 
-    if !out.status.success() {
-        return Err(format_err!("bass error: {}", out.status));
+fn assemble(src_files: &[PathBuf]) -> Result<(Vec<Hunk>, SymbolTable), Error> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut symbols = SymbolTable::new();
+    for file in src_files {
+        let mut assembly = asm::assemble_file(file)?;
+        hunks.append(&mut assembly.hunks);
+        merge_symbols(&mut symbols, assembly.symbols, file)?;
     }
 
+    Ok((hunks, symbols))
+}
+
+/// Write `hunks` over `data`.
+///
+/// Fails without writing anything if a hunk runs past the end of `data`.
+pub fn apply_hunks(data: &mut [u8], hunks: &[Hunk]) -> Result<(), Error> {
+    for hunk in hunks {
+        let start = hunk.offset as usize;
+        let end = start + hunk.data.len();
+        if end > data.len() {
+            return Err(format_err!(
+                "hunk {:06x}-{:06x} is past the end of the {} byte source",
+                start,
+                end,
+                data.len()
+            ));
+        }
+    }
+    for hunk in hunks {
+        let start = hunk.offset as usize;
+        data[start..start + hunk.data.len()].copy_from_slice(&hunk.data);
+    }
     Ok(())
 }
 
-#[derive(Debug)]
-struct Hunk {
-    offset: u32,
-    data: Vec<u8>,
+/// Assemble `src_files` into an IPS patch, returning their labels.
+pub fn build(
+    src_files: &[PathBuf],
+    out: &Path,
+    truncate: Option<u32>,
+) -> Result<SymbolTable, Error> {
+    let (hunks, symbols) = assemble(src_files)?;
+
+    let mut f = File::create(out)
+        .map_err(|e| format_err!("failed to create ips file {}: {}", out.to_string_lossy(), e))?;
+    ips::write_ips(&mut f, &hunks, truncate)?;
+
+    Ok(symbols)
 }
 
-fn diff_files(zeros_path: &PathBuf, effs_path: &PathBuf) -> Result<Vec<Hunk>, Error> {
-    let mut f = File::open(zeros_path)?;
-    let mut zeros = Vec::new();
-    f.read_to_end(&mut zeros)?;
-
-    let mut f = File::open(effs_path)?;
-    let mut effs = Vec::new();
-    f.read_to_end(&mut effs)?;
-
-    if zeros.len() != effs.len() {
-        return Err(format_err!(
-            "Can't diff.  File lengths differ {} != {}",
-            zeros.len(),
-            effs.len()
-        ));
-    }
+/// Assemble `src_files` into a BPS patch against the ROM at `source_path`.
+///
+/// Unlike IPS, BPS patches record checksums of the ROM they apply to so the
+/// source ROM is needed to build one.
+pub fn build_bps(
+    source_path: &Path,
+    src_files: &[PathBuf],
+    out: &Path,
+) -> Result<SymbolTable, Error> {
+    let source = read_file(source_path)?;
+    let (hunks, symbols) = assemble(src_files)?;
+    let mut target = source.clone();
+    apply_hunks(&mut target, &hunks)?;
 
-    let mut last_match = 0;
-    let mut hunks: Vec<Hunk> = Vec::new();
+    let patch = bps::create_patch(&source, &target, "");
+    let mut f = File::create(out)
+        .map_err(|e| format_err!("failed to create bps file {}: {}", out.to_string_lossy(), e))?;
+    f.write_all(&patch)?;
+
+    Ok(symbols)
+}
 
-    for (offset, (a, b)) in zeros.iter().zip(effs.iter()).enumerate() {
-        if a == b {
-            if (last_match + 1) == offset {
-                let mut hunk = hunks.pop().unwrap();
-                hunk.data.push(*a);
-                hunks.push(hunk);
-            } else {
-                hunks.push(Hunk {
-                    offset: offset as u32,
-                    data: vec![*a],
-                });
-            }
-            last_match = offset;
+/// Write `symbols` as a JSON object keyed by label name.
+pub fn write_symbols_json(w: &mut impl Write, symbols: &SymbolTable) -> Result<(), Error> {
+    serde_json::to_writer_pretty(&mut *w, symbols)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+// Converts a label or file name to a Rust identifier.
+fn rust_ident(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Write `symbols` as a Rust module named `module`.
+///
+/// Each label becomes a `LABEL` constant holding its CPU address and a
+/// `LABEL_OFFSET` constant holding its file offset.  Slots also get a
+/// `LABEL_SIZE` constant and are listed in `SLOTS` as
+/// `(name, offset, size)`.  All labels are listed in `SYMBOLS` as
+/// `(name, address, offset)`.
+pub fn write_symbols_rust(
+    w: &mut impl Write,
+    module: &str,
+    symbols: &SymbolTable,
+) -> Result<(), Error> {
+    writeln!(w, "pub mod {} {{", rust_ident(module))?;
+    for (name, symbol) in symbols {
+        let name = rust_ident(name).to_ascii_uppercase();
+        writeln!(w, "    pub const {}: u32 = {:#06x};", name, symbol.address)?;
+        writeln!(
+            w,
+            "    pub const {}_OFFSET: usize = {:#07x};",
+            name, symbol.offset
+        )?;
+        if let Some(size) = symbol.size {
+            writeln!(w, "    pub const {}_SIZE: usize = {};", name, size)?;
         }
     }
 
-    Ok(hunks)
-}
+    writeln!(w, "    pub const SYMBOLS: &[(&str, u32, usize)] = &[")?;
+    for (name, symbol) in symbols {
+        writeln!(
+            w,
+            "        ({:?}, {:#06x}, {:#07x}),",
+            name, symbol.address, symbol.offset
+        )?;
+    }
+    writeln!(w, "    ];")?;
 
-fn write_ips(w: &mut impl Write, hunks: &[Hunk]) -> Result<(), Error> {
-    w.write_all(b"PATCH")?;
-    for hunk in hunks {
-        w.write_u24::<BigEndian>(hunk.offset)?;
-        w.write_u16::<BigEndian>(hunk.data.len() as u16)?;
-        w.write_all(&hunk.data)?;
+    writeln!(w, "    pub const SLOTS: &[(&str, usize, usize)] = &[")?;
+    for (name, symbol) in symbols {
+        if let Some(size) = symbol.size {
+            writeln!(w, "        ({:?}, {:#07x}, {}),", name, symbol.offset, size)?;
+        }
     }
-    w.write_all(b"EOF")?;
+    writeln!(w, "    ];")?;
+    writeln!(w, "}}")?;
     Ok(())
 }
 
-pub fn build(
-    bass: &PathBuf,
-    rom_size: u32,
-    tmp_dir: &PathBuf,
-    src_files: &[PathBuf],
-    out: &PathBuf,
-) -> Result<(), Error> {
-    let zeros_path = tmp_dir.join("00.bin");
-    let effs_path = tmp_dir.join("ff.bin");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut hunks: Vec<Hunk> = Vec::new();
-    for file in src_files {
-        make_target_file(&zeros_path, rom_size as usize, 0x00)?;
-        make_target_file(&effs_path, rom_size as usize, 0xff)?;
-        run_bass(bass, &file, &zeros_path)?;
-        run_bass(bass, &file, &effs_path)?;
-        let mut new_hunks = diff_files(&zeros_path, &effs_path)?;
-        hunks.append(&mut new_hunks);
+    fn hunk(offset: u32, data: &[u8]) -> Hunk {
+        Hunk {
+            offset,
+            data: data.to_vec(),
+        }
     }
 
-    let mut f = File::create(out)
-        .map_err(|e| format_err!("failed to create ips file {}: {}", out.to_string_lossy(), e))?;
-    write_ips(&mut f, &hunks)?;
+    #[test]
+    fn test_apply_hunks_bps() {
+        let source = vec![0x11u8; 0x100];
+        let hunks = vec![hunk(0x10, &[1, 2, 3]), hunk(0xff, &[4])];
+        let mut target = source.clone();
+        apply_hunks(&mut target, &hunks).unwrap();
+        assert_eq!(&target[0x10..0x13], &[1, 2, 3]);
+        assert_eq!(target[0xff], 4);
 
-    Ok(())
+        let patch = bps::create_patch(&source, &target, "");
+        assert_eq!(bps::apply_patch(&source, &patch).unwrap(), target);
+
+        let mut data = source.clone();
+        assert!(apply_hunks(&mut data, &[hunk(0x10, &[5]), hunk(0xff, &[1, 2])]).is_err());
+        assert_eq!(data, source);
+    }
+
+    #[test]
+    fn test_merge_symbols() {
+        let symbol = |address| Symbol {
+            address,
+            offset: address,
+            size: None,
+        };
+        let mut symbols = SymbolTable::new();
+        let a: SymbolTable = vec![("a".to_string(), symbol(1))].into_iter().collect();
+        let b: SymbolTable = vec![("b".to_string(), symbol(2))].into_iter().collect();
+        merge_symbols(&mut symbols, a.clone(), Path::new("a.asm")).unwrap();
+        merge_symbols(&mut symbols, b, Path::new("b.asm")).unwrap();
+        assert_eq!(symbols.len(), 2);
+
+        let err = merge_symbols(&mut symbols, a, Path::new("c.asm")).unwrap_err();
+        assert_eq!(err.to_string(), "label a in c.asm is already defined");
+    }
+
+    #[test]
+    fn test_write_symbols() {
+        let mut symbols = SymbolTable::new();
+        symbols.insert(
+            "get_sword_handler".to_string(),
+            Symbol {
+                address: 0xddf1,
+                offset: 0x7df1,
+                size: None,
+            },
+        );
+        symbols.insert(
+            "starting_bombs".to_string(),
+            Symbol {
+                address: 0xdf80,
+                offset: 0x7f80,
+                size: Some(1),
+            },
+        );
+
+        let mut rust = Vec::new();
+        write_symbols_rust(&mut rust, "no-downgrade", &symbols).unwrap();
+        assert_eq!(
+            String::from_utf8(rust).unwrap(),
+            "pub mod no_downgrade {\n\
+             \x20   pub const GET_SWORD_HANDLER: u32 = 0xddf1;\n\
+             \x20   pub const GET_SWORD_HANDLER_OFFSET: usize = 0x07df1;\n\
+             \x20   pub const STARTING_BOMBS: u32 = 0xdf80;\n\
+             \x20   pub const STARTING_BOMBS_OFFSET: usize = 0x07f80;\n\
+             \x20   pub const STARTING_BOMBS_SIZE: usize = 1;\n\
+             \x20   pub const SYMBOLS: &[(&str, u32, usize)] = &[\n\
+             \x20       (\"get_sword_handler\", 0xddf1, 0x07df1),\n\
+             \x20       (\"starting_bombs\", 0xdf80, 0x07f80),\n\
+             \x20   ];\n\
+             \x20   pub const SLOTS: &[(&str, usize, usize)] = &[\n\
+             \x20       (\"starting_bombs\", 0x07f80, 1),\n\
+             \x20   ];\n\
+             }\n"
+        );
+
+        let mut json = Vec::new();
+        write_symbols_json(&mut json, &symbols).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(parsed["get_sword_handler"]["address"], 0xddf1);
+        assert_eq!(parsed["get_sword_handler"]["offset"], 0x7df1);
+        assert!(parsed["get_sword_handler"].get("size").is_none());
+        assert_eq!(parsed["starting_bombs"]["size"], 1);
+    }
 }
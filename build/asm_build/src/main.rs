@@ -1,7 +1,9 @@
+use std::fs::File;
 use std::num::ParseIntError;
 use std::path::PathBuf;
+use std::str::FromStr;
 
-use failure::Error;
+use failure::{format_err, Error};
 use parse_int::parse;
 use structopt::StructOpt;
 
@@ -9,19 +11,43 @@ fn parse_num(src: &str) -> Result<u32, ParseIntError> {
     parse::<u32>(src)
 }
 
+#[derive(Debug)]
+enum Format {
+    Ips,
+    Bps,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "ips" => Ok(Format::Ips),
+            "bps" => Ok(Format::Bps),
+            _ => Err(format_err!("Could not parse patch format")),
+        }
+    }
+}
+
 #[derive(StructOpt, Debug)]
 struct Opt {
     #[structopt(long, parse(from_os_str), default_value = "patch.ips")]
     out: PathBuf,
 
-    #[structopt(long, parse(try_from_str = parse_num))]
-    rom_size: u32,
+    /// Patch format: ips or bps.
+    #[structopt(long, default_value = "ips")]
+    format: Format,
+
+    /// ROM the patch applies to.  Required for BPS output.
+    #[structopt(long, parse(from_os_str))]
+    source: Option<PathBuf>,
 
-    #[structopt(long, parse(from_os_str), default_value = "bass")]
-    bass: PathBuf,
+    /// Length to truncate the patched file to.  IPS only.
+    #[structopt(long, parse(try_from_str = parse_num))]
+    truncate: Option<u32>,
 
-    #[structopt(long, parse(from_os_str), default_value = ".")]
-    tmp_dir: PathBuf,
+    /// Write label addresses to this JSON file.
+    #[structopt(long, parse(from_os_str))]
+    symbols: Option<PathBuf>,
 
     #[structopt(parse(from_os_str))]
     src_files: Vec<PathBuf>,
@@ -30,13 +56,23 @@ struct Opt {
 fn main() -> Result<(), Error> {
     let opt = Opt::from_args();
 
-    asm_build::build(
-        &opt.bass,
-        opt.rom_size,
-        &opt.tmp_dir,
-        &opt.src_files,
-        &opt.out,
-    )?;
+    let symbols = match opt.format {
+        Format::Ips => asm_build::build(&opt.src_files, &opt.out, opt.truncate)?,
+        Format::Bps => {
+            let source = opt
+                .source
+                .ok_or_else(|| format_err!("--source is required for bps output"))?;
+            if opt.truncate.is_some() {
+                return Err(format_err!("--truncate is only supported for ips output"));
+            }
+            asm_build::build_bps(&source, &opt.src_files, &opt.out)?
+        }
+    };
+
+    if let Some(path) = &opt.symbols {
+        let mut f = File::create(path)?;
+        asm_build::write_symbols_json(&mut f, &symbols)?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,51 @@
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+use failure::{format_err, Error};
+use structopt::StructOpt;
+
+use neutopia::{bps, ips};
+
+#[derive(StructOpt, Debug)]
+pub(crate) struct ApplyOpt {
+    /// Base ROM the patch was made against.
+    #[structopt(long, parse(from_os_str), default_value = "Neutopia (USA).pce")]
+    rom: PathBuf,
+
+    /// IPS or BPS patch to apply.
+    #[structopt(long, parse(from_os_str))]
+    patch: PathBuf,
+
+    #[structopt(long, parse(from_os_str), default_value = "out.pce")]
+    out: PathBuf,
+}
+
+pub(crate) fn command(opt: &ApplyOpt) -> Result<(), Error> {
+    let (base, info) = crate::read_supported_rom(&opt.rom)?;
+    let patch = fs::read(&opt.patch)
+        .map_err(|e| format_err!("unable to open {}: {}", opt.patch.display(), e))?;
+
+    let data = if patch.starts_with(b"BPS1") {
+        let patch_info = bps::patch_info(&patch)?;
+        if !patch_info.metadata.is_empty() {
+            println!("{}", patch_info.metadata);
+        }
+        bps::apply_patch(&base, &patch)?
+    } else if patch.starts_with(b"PATCH") {
+        ips::apply_patch(&base, &patch)?
+    } else {
+        return Err(format_err!(
+            "{} is not an IPS or BPS patch",
+            opt.patch.display()
+        ));
+    };
+
+    let mut f = File::create(&opt.out)?;
+    f.write_all(&data)?;
+
+    println!("applied {} to {}", opt.patch.display(), info.desc);
+    println!("wrote {}", opt.out.display());
+
+    Ok(())
+}
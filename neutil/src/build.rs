@@ -0,0 +1,312 @@
+//! Builds a ROM from a project directory.
+//!
+//! A project keeps a ROM hack as text so it can live in version control.
+//! `project.json` at its root lists the files that make it up:
+//!
+//! ```json
+//! {
+//!     "data": "data.ron",
+//!     "asm": ["asm/title.asm", "asm/shop.asm"]
+//! }
+//! ```
+//!
+//! `data` is game data written by `neutil export` and `asm` are patches in
+//! the same syntax as the randomizer's.  Both are optional and paths are
+//! relative to the project directory.  The patches are assembled onto the
+//! base ROM first and then the game data is written with `Neutopia::write`.
+//! A patch may not touch the game data tables or any range the data ends
+//! up in.
+
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use failure::{format_err, Error};
+use serde::Deserialize;
+use structopt::StructOpt;
+
+use asm_build::SymbolTable;
+use neutopia::{Ledger, Neutopia, NeutopiaRom};
+
+const MANIFEST: &str = "project.json";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    data: Option<PathBuf>,
+    #[serde(default)]
+    asm: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Project {
+    dir: PathBuf,
+    manifest: Manifest,
+}
+
+/// Output of building a project.
+pub(crate) struct Build {
+    /// Un-headered ROM.
+    pub data: Vec<u8>,
+    /// Labels from every asm file.
+    pub symbols: SymbolTable,
+}
+
+impl Project {
+    pub(crate) fn open(dir: &Path) -> Result<Self, Error> {
+        let path = dir.join(MANIFEST);
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format_err!("unable to read {}: {}", path.display(), e))?;
+        let manifest = serde_json::from_str(&text)
+            .map_err(|e| format_err!("can't parse {}: {}", path.display(), e))?;
+        Ok(Project {
+            dir: dir.to_path_buf(),
+            manifest,
+        })
+    }
+
+    // Assembles the project's patches onto `data`, claiming what each
+    // writes in `ledger`.
+    fn apply_asm(&self, data: &mut [u8], ledger: &mut Ledger) -> Result<SymbolTable, Error> {
+        let tables = NeutopiaRom::new(data)?.data_intervals().get_intervals();
+        let mut symbols = SymbolTable::new();
+
+        for file in &self.manifest.asm {
+            let path = self.dir.join(file);
+            let owner = format!("asm {}", file.display());
+            let assembly = asm_build::asm::assemble_file(&path)?;
+
+            for hunk in &assembly.hunks {
+                let start = hunk.offset as usize;
+                let end = start + hunk.data.len();
+                // The data file replaces these tables so changes to them
+                // would be lost.
+                if tables.iter().any(|i| i.start < end && start < i.end) {
+                    return Err(format_err!(
+                        "{} writes {:05x}-{:05x} which holds game data, edit {} instead",
+                        owner,
+                        start,
+                        end,
+                        self.data_name()
+                    ));
+                }
+                ledger.claim(&owner, start, end)?;
+            }
+            asm_build::apply_hunks(data, &assembly.hunks)
+                .map_err(|e| format_err!("{}: {}", owner, e))?;
+            asm_build::merge_symbols(&mut symbols, assembly.symbols, file)?;
+        }
+
+        Ok(symbols)
+    }
+
+    fn data_name(&self) -> String {
+        match &self.manifest.data {
+            Some(path) => path.display().to_string(),
+            None => "a data file".to_string(),
+        }
+    }
+
+    /// Build the project on top of the un-headered ROM `base`.
+    pub(crate) fn build(&self, base: &[u8]) -> Result<Build, Error> {
+        let mut data = base.to_vec();
+        let mut ledger = Ledger::new();
+        let symbols = self.apply_asm(&mut data, &mut ledger)?;
+
+        if let Some(file) = &self.manifest.data {
+            let mut n = Neutopia::new(&data)?;
+            n.import(crate::export::read_data(&self.dir.join(file))?)
+                .map_err(|e| format_err!("{}: {}", file.display(), e))?;
+            data = n.write_tracked(&mut ledger)?;
+        }
+
+        Ok(Build { data, symbols })
+    }
+}
+
+#[derive(StructOpt, Debug)]
+pub(crate) struct BuildOpt {
+    /// Unmodified ROM to build on.
+    #[structopt(long, parse(from_os_str), default_value = "Neutopia (USA).pce")]
+    rom: PathBuf,
+
+    /// Directory holding project.json.
+    #[structopt(long, parse(from_os_str), default_value = ".")]
+    project: PathBuf,
+
+    #[structopt(long, parse(from_os_str), default_value = "out.pce")]
+    out: PathBuf,
+
+    /// Write the labels from the project's asm files here as JSON.
+    #[structopt(long, parse(from_os_str))]
+    symbols: Option<PathBuf>,
+}
+
+pub(crate) fn command(opt: &BuildOpt) -> Result<(), Error> {
+    let (rom, info) = crate::read_known_rom(&opt.rom)?;
+
+    let project = Project::open(&opt.project)?;
+    let build = project.build(&rom)?;
+
+    let mut f = File::create(&opt.out)?;
+    f.write_all(&build.data)?;
+    if let Some(path) = &opt.symbols {
+        let mut f = File::create(path)?;
+        asm_build::write_symbols_json(&mut f, &build.symbols)?;
+    }
+
+    println!("built {} on {}", opt.project.display(), info.desc);
+    println!("wrote {}", opt.out.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use super::*;
+    use crate::export::Format;
+    use neutopia::synthetic::{RomBuilder, RoomSpec};
+
+    // A scratch project directory that is removed when dropped.
+    struct TestProject(PathBuf);
+
+    impl TestProject {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = env::temp_dir().join(format!("neutil-build-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("asm")).unwrap();
+            for (path, contents) in files {
+                fs::write(dir.join(path), contents).unwrap();
+            }
+            TestProject(dir)
+        }
+
+        fn build(&self, base: &[u8]) -> Result<Build, Error> {
+            Project::open(&self.0)?.build(base)
+        }
+    }
+
+    impl Drop for TestProject {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn base() -> Vec<u8> {
+        RomBuilder::new()
+            .room(
+                4,
+                0,
+                RoomSpec {
+                    enemies: vec![0x01, 0x01],
+                    ..Default::default()
+                },
+            )
+            .build()
+            .unwrap()
+    }
+
+    fn data_file(base: &[u8]) -> String {
+        let mut data = Neutopia::new(base).unwrap().export();
+        data.areas[4].rooms[0].enemies = vec![0x02, 0x03];
+        Format::Ron.serialize(&data).unwrap()
+    }
+
+    #[test]
+    fn test_build() {
+        let base = base();
+        let project = TestProject::new(
+            "ok",
+            &[
+                (
+                    "project.json",
+                    r#"{"data": "data.ron", "asm": ["asm/a.asm", "asm/b.asm"]}"#,
+                ),
+                ("data.ron", &data_file(&base)),
+                (
+                    "asm/a.asm",
+                    "arch pce.cpu\norigin $1000\nstart:\ndb $12, $34\n",
+                ),
+                ("asm/b.asm", "arch pce.cpu\norigin $1002\nnext:\ndb $56\n"),
+            ],
+        );
+
+        let build = project.build(&base).unwrap();
+        assert_eq!(build.data[0x1000..0x1003], [0x12, 0x34, 0x56]);
+        assert_eq!(build.symbols["start"].offset, 0x1000);
+        assert_eq!(build.symbols["next"].offset, 0x1002);
+
+        let n = Neutopia::new(&build.data).unwrap();
+        assert_eq!(n.areas[4].rooms[0].enemies, vec![0x02, 0x03]);
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let base = base();
+        let err =
+            |name: &str, files: &[(&str, &str)]| match TestProject::new(name, files).build(&base) {
+                Ok(_) => panic!("{} built", name),
+                Err(e) => e.to_string(),
+            };
+
+        let e = err(
+            "overlap",
+            &[
+                ("project.json", r#"{"asm": ["asm/a.asm", "asm/b.asm"]}"#),
+                ("asm/a.asm", "arch pce.cpu\norigin $1000\ndb $12, $34\n"),
+                ("asm/b.asm", "arch pce.cpu\norigin $1001\ndb $56\n"),
+            ],
+        );
+        assert_eq!(
+            e,
+            "asm asm/b.asm (01001-01002) overlaps asm asm/a.asm (01000-01002)"
+        );
+
+        let e = err(
+            "tables",
+            &[
+                ("project.json", r#"{"asm": ["asm/a.asm"]}"#),
+                ("asm/a.asm", "arch pce.cpu\norigin $50000\ndb $12\n"),
+            ],
+        );
+        assert_eq!(
+            e,
+            "asm asm/a.asm writes 50000-50001 which holds game data, edit a data file instead"
+        );
+
+        // Relocated chest tables are written over the patch.
+        let e = err(
+            "relocated",
+            &[
+                (
+                    "project.json",
+                    r#"{"data": "data.ron", "asm": ["asm/a.asm"]}"#,
+                ),
+                ("data.ron", &data_file(&base)),
+                ("asm/a.asm", "arch pce.cpu\norigin $4fe90\ndb $12\n"),
+            ],
+        );
+        assert!(
+            e.starts_with("area 04 chest table (4fe80-4fea0) overlaps asm asm/a.asm"),
+            "{}",
+            e
+        );
+
+        let e = err(
+            "past end",
+            &[
+                ("project.json", r#"{"asm": ["asm/a.asm"]}"#),
+                ("asm/a.asm", "arch pce.cpu\norigin $5ffff\ndb $12, $34\n"),
+            ],
+        );
+        assert_eq!(
+            e,
+            "asm asm/a.asm: hunk 05ffff-060001 is past the end of the 393216 byte source"
+        );
+
+        let e = err("manifest", &[("project.json", r#"{"patches": []}"#)]);
+        assert!(e.contains("unknown field `patches`"), "{}", e);
+    }
+}
@@ -29,8 +29,8 @@ pub(crate) fn command(opt: &ChecksOpt) -> Result<(), Error> {
         // All areas that are non the end game area.
         (chest.area < 0x10)
                 // Chest does not contain medallion
-                && (chest.info.item_id < 0x12 || chest.info.item_id >= (0x12 + 8))
-    });
+                && !chest.info.item().is_medallion()
+    })?;
 
     let mut checks = Vec::new();
     for chest in &chests {
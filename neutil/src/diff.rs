@@ -0,0 +1,31 @@
+use std::path::{Path, PathBuf};
+
+use failure::{format_err, Error};
+use structopt::StructOpt;
+
+use neutopia::{diff, Neutopia};
+
+#[derive(StructOpt, Debug)]
+pub(crate) struct DiffOpt {
+    /// ROM to compare from, usually the vanilla game.
+    #[structopt(parse(from_os_str))]
+    a: PathBuf,
+
+    /// ROM to compare to, e.g. a randomized seed.
+    #[structopt(parse(from_os_str))]
+    b: PathBuf,
+}
+
+fn load(path: &Path) -> Result<Neutopia, Error> {
+    Neutopia::new(&crate::read_rom(path)?).map_err(|e| format_err!("{}: {}", path.display(), e))
+}
+
+pub(crate) fn command(opt: &DiffOpt) -> Result<(), Error> {
+    let d = diff::diff(&load(&opt.a)?, &load(&opt.b)?);
+    if d.is_empty() {
+        println!("no differences");
+    } else {
+        print!("{}", d);
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::path::PathBuf;
+
+use failure::{format_err, Error};
+use serde::Deserialize;
+use structopt::StructOpt;
+
+use neutopia::bank::{self, Address};
+use neutopia::disasm::{self, Change, Labels};
+use neutopia::interval::IntervalStore;
+use neutopia::NeutopiaRom;
+
+#[derive(StructOpt, Debug)]
+pub(crate) struct DisasmOpt {
+    #[structopt(long, parse(from_os_str), default_value = "Neutopia (USA).pce")]
+    rom: PathBuf,
+
+    /// Show only the instructions that differ from this ROM.
+    #[structopt(long, parse(from_os_str))]
+    vanilla: Option<PathBuf>,
+
+    /// HuCard bank to disassemble (hex).  The last 128K of the ROM is
+    /// banks 40-4f.
+    #[structopt(long, parse(try_from_str = crate::parse_hex))]
+    bank: u32,
+
+    /// CPU address to start at (hex).  The bank is assumed to be mapped
+    /// into the page holding this address.
+    #[structopt(long, parse(try_from_str = crate::parse_hex))]
+    address: u32,
+
+    /// Number of bytes to disassemble (hex).
+    #[structopt(long, parse(try_from_str = crate::parse_hex), default_value = "40")]
+    len: u32,
+
+    /// Symbol files written by asm-build to take labels from.  Labels from
+    /// the patches recorded in the ROM are always used.
+    #[structopt(long, parse(from_os_str))]
+    symbols: Vec<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct Symbol {
+    address: u32,
+    offset: usize,
+}
+
+fn load_labels(opt: &DisasmOpt, rom: &[u8], start: &Address) -> Result<Labels, Error> {
+    let bank_start = bank::bank_offset(start.bank)?;
+    let in_bank = |offset: usize| (bank_start..bank_start + bank::BANK_SIZE).contains(&offset);
+    let mut labels = Labels::new();
+
+    let applied = rando::patches::read_applied(rom).unwrap_or_default();
+    for patch in rando::patches::PATCHES.iter() {
+        if !applied.iter().any(|name| name == patch.name) {
+            continue;
+        }
+        for (name, address, offset) in patch.symbols {
+            if in_bank(*offset) {
+                labels.insert(*address, name.to_string());
+            }
+        }
+    }
+
+    for path in &opt.symbols {
+        let f = File::open(path)
+            .map_err(|e| format_err!("unable to open {}: {}", path.display(), e))?;
+        let symbols: BTreeMap<String, Symbol> = serde_json::from_reader(f)?;
+        for (name, symbol) in symbols {
+            if in_bank(symbol.offset) {
+                labels.insert(symbol.address, name);
+            }
+        }
+    }
+
+    Ok(labels)
+}
+
+// Known data tables of `rom`, which are listed as bytes instead of code.
+fn data_regions(rom: &[u8]) -> Result<IntervalStore<usize>, Error> {
+    let n = NeutopiaRom::new(rom).map_err(|e| format_err!("can't find data tables: {}", e))?;
+    Ok(disasm::data_regions(&n))
+}
+
+pub(crate) fn command(opt: &DisasmOpt) -> Result<(), Error> {
+    let rom = crate::read_rom(&opt.rom)?;
+    let start = Address::new(
+        u8::try_from(opt.bank).map_err(|_| format_err!("bank {:x} out of range", opt.bank))?,
+        u16::try_from(opt.address)
+            .map_err(|_| format_err!("address {:x} out of range", opt.address))?,
+    );
+    let offset = start.offset()?;
+    let labels = load_labels(opt, &rom, &start)?;
+    let regions = data_regions(&rom)?;
+
+    match &opt.vanilla {
+        Some(vanilla) => {
+            let vanilla = crate::read_rom(vanilla)?;
+            let vanilla_regions = data_regions(&vanilla)?;
+            let groups = disasm::diff(
+                (&vanilla, &vanilla_regions),
+                (&rom, &regions),
+                offset,
+                opt.address,
+                opt.len as usize,
+            );
+            for group in groups {
+                for change in group {
+                    match change {
+                        Change::Removed(i) => println!("- {}", i.listing(&labels)),
+                        Change::Added(i) => println!("+ {}", i.listing(&labels)),
+                    }
+                }
+                println!();
+            }
+        }
+        None => {
+            for i in disasm::disassemble(&rom, offset, opt.address, opt.len as usize, &regions) {
+                if let Some(label) = labels.get(&i.address) {
+                    println!("{}:", label);
+                }
+                println!("{}", i.listing(&labels));
+            }
+        }
+    }
+
+    Ok(())
+}
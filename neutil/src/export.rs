@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use failure::{format_err, Error};
+use structopt::StructOpt;
+
+use neutopia::{export::GameData, Neutopia};
+
+/// Text formats game data can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Format {
+    Json,
+    Ron,
+}
+
+impl Format {
+    /// Picks the format from the extension of `path`.
+    pub(crate) fn from_path(path: &Path) -> Result<Self, Error> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Ok(Format::Json),
+            Some("ron") => Ok(Format::Ron),
+            _ => Err(format_err!(
+                "can't tell the format of {}, use a .json or .ron extension",
+                path.display()
+            )),
+        }
+    }
+
+    pub(crate) fn serialize(&self, data: &GameData) -> Result<String, Error> {
+        Ok(match self {
+            Format::Json => serde_json::to_string_pretty(data)?,
+            Format::Ron => ron::ser::to_string_pretty(data, ron::ser::PrettyConfig::default())?,
+        })
+    }
+
+    pub(crate) fn deserialize(&self, text: &str) -> Result<GameData, Error> {
+        Ok(match self {
+            Format::Json => serde_json::from_str(text)?,
+            Format::Ron => ron::from_str(text)?,
+        })
+    }
+}
+
+/// Read game data from `path` in the format given by its extension.
+pub(crate) fn read_data(path: &Path) -> Result<GameData, Error> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut text))
+        .map_err(|e| format_err!("unable to read {}: {}", path.display(), e))?;
+    Format::from_path(path)?
+        .deserialize(&text)
+        .map_err(|e| format_err!("can't parse {}: {}", path.display(), e))
+}
+
+#[derive(StructOpt, Debug)]
+pub(crate) struct ExportOpt {
+    #[structopt(long, parse(from_os_str), default_value = "Neutopia (USA).pce")]
+    rom: PathBuf,
+
+    /// File to write, ending in .json or .ron.
+    #[structopt(long, parse(from_os_str), default_value = "neutopia.ron")]
+    out: PathBuf,
+}
+
+pub(crate) fn command(opt: &ExportOpt) -> Result<(), Error> {
+    let format = Format::from_path(&opt.out)?;
+    let n = Neutopia::new(&crate::read_rom(&opt.rom)?)?;
+
+    let mut f = File::create(&opt.out)?;
+    f.write_all(format.serialize(&n.export())?.as_bytes())?;
+
+    println!("wrote {}", opt.out.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use neutopia::synthetic::RomBuilder;
+
+    #[test]
+    fn test_formats() {
+        let n = Neutopia::new(&RomBuilder::new().build().unwrap()).unwrap();
+        let data = n.export();
+        for format in &[Format::Json, Format::Ron] {
+            let text = format.serialize(&data).unwrap();
+            let parsed = format.deserialize(&text).unwrap();
+            assert_eq!(format.serialize(&parsed).unwrap(), text);
+        }
+
+        assert_eq!(
+            Format::from_path(Path::new("a/game.ron")).unwrap(),
+            Format::Ron
+        );
+        assert!(Format::from_path(Path::new("game.txt")).is_err());
+    }
+}
@@ -0,0 +1,35 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+use failure::{format_err, Error};
+use structopt::StructOpt;
+
+use neutopia::Neutopia;
+
+#[derive(StructOpt, Debug)]
+pub(crate) struct ImportOpt {
+    /// ROM to write the data into.
+    #[structopt(long, parse(from_os_str), default_value = "Neutopia (USA).pce")]
+    rom: PathBuf,
+
+    /// Game data written by `neutil export`.
+    #[structopt(long, parse(from_os_str), default_value = "neutopia.ron")]
+    data: PathBuf,
+
+    #[structopt(long, parse(from_os_str), default_value = "out.pce")]
+    out: PathBuf,
+}
+
+pub(crate) fn command(opt: &ImportOpt) -> Result<(), Error> {
+    let mut n = Neutopia::new(&crate::read_rom(&opt.rom)?)?;
+    n.import(crate::export::read_data(&opt.data)?)
+        .map_err(|e| format_err!("{}: {}", opt.data.display(), e))?;
+    let data = n.write()?;
+
+    let mut f = File::create(&opt.out)?;
+    f.write_all(&data)?;
+
+    println!("wrote {}", opt.out.display());
+    Ok(())
+}
@@ -5,7 +5,7 @@ use std::path::PathBuf;
 use failure::Error;
 use structopt::StructOpt;
 
-use neutopia::verify;
+use neutopia::{bank, verify};
 
 #[derive(StructOpt, Debug)]
 pub(crate) struct InfoOpt {
@@ -20,12 +20,16 @@ pub(crate) fn command(opt: &InfoOpt) -> Result<(), Error> {
     f.read_to_end(&mut buffer)?;
 
     let info = verify(&buffer)?;
+    let header_len = bank::file_offset(0, info.headered);
 
     println!("Info for {}:", &opt.rom.display());
     println!("  Headered:    {}", info.headered);
     println!("  MD5 hash:    {}", info.md5_hash);
     println!("  Description: {}", info.desc);
     println!("  Region:      {:?}", info.region);
+    if let Some(patches) = rando::patches::read_applied(&buffer[header_len..]) {
+        println!("  Patches:     {}", patches.join(", "));
+    }
 
     Ok(())
 }
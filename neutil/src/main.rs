@@ -1,25 +1,95 @@
-use failure::Error;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use failure::{format_err, Error};
 use structopt::StructOpt;
 
+use neutopia::{bank, verify, verify_known, verify_supported, RomInfo};
+
+mod apply;
+mod build;
 mod checks;
+mod diff;
+mod disasm;
 mod doc;
+mod export;
+mod import;
 mod info;
 mod password;
+mod render;
+mod roundtrip;
+
+// Reads a ROM, checking it with `verify` and removing its header if it has
+// one.
+fn read_verified_rom(
+    path: &Path,
+    verify: fn(&[u8]) -> Result<RomInfo, Error>,
+) -> Result<(Vec<u8>, RomInfo), Error> {
+    let mut f =
+        File::open(path).map_err(|e| format_err!("unable to open {}: {}", path.display(), e))?;
+    let mut data = Vec::new();
+    f.read_to_end(&mut data)?;
+
+    let info = verify(&data)?;
+    if info.headered {
+        data.drain(..bank::HEADER_LEN);
+    }
+    Ok((data, info))
+}
+
+// Reads a ROM, removing its header if it has one.
+pub(crate) fn read_rom(path: &Path) -> Result<Vec<u8>, Error> {
+    Ok(read_verified_rom(path, verify)?.0)
+}
+
+// Reads a ROM like `read_rom`, failing unless it is a known dump.
+pub(crate) fn read_known_rom(path: &Path) -> Result<(Vec<u8>, RomInfo), Error> {
+    read_verified_rom(path, verify_known)
+}
+
+// Reads a ROM like `read_rom`, failing unless it is the NA release that the
+// randomizer and its patches are made against.
+pub(crate) fn read_supported_rom(path: &Path) -> Result<(Vec<u8>, RomInfo), Error> {
+    read_verified_rom(path, verify_supported)
+}
+
+// Parses a hex command line argument, with or without a `0x` or `$` prefix.
+pub(crate) fn parse_hex(src: &str) -> Result<u32, Error> {
+    let digits = src.trim_start_matches("0x").trim_start_matches('$');
+    u32::from_str_radix(digits, 16).map_err(|e| format_err!("invalid number {}: {}", src, e))
+}
 
 #[derive(StructOpt, Debug)]
 enum Opt {
+    Apply(apply::ApplyOpt),
+    Build(build::BuildOpt),
     Checks(checks::ChecksOpt),
+    Diff(diff::DiffOpt),
+    Disasm(disasm::DisasmOpt),
     Doc(doc::DocOpt),
+    Export(export::ExportOpt),
+    Import(import::ImportOpt),
     Info(info::InfoOpt),
     Password(password::PasswordOpt),
+    Render(render::RenderOpt),
+    Roundtrip(roundtrip::RoundtripOpt),
 }
 
 fn main() -> Result<(), Error> {
     let opt = Opt::from_args();
     match &opt {
+        Opt::Apply(apply_opt) => apply::command(apply_opt),
+        Opt::Build(build_opt) => build::command(build_opt),
         Opt::Checks(checks_opt) => checks::command(checks_opt),
+        Opt::Diff(diff_opt) => diff::command(diff_opt),
+        Opt::Disasm(disasm_opt) => disasm::command(disasm_opt),
         Opt::Doc(doc_opt) => doc::command(doc_opt),
+        Opt::Export(export_opt) => export::command(export_opt),
+        Opt::Import(import_opt) => import::command(import_opt),
         Opt::Info(info_opt) => info::command(info_opt),
         Opt::Password(password_opt) => password::command(password_opt),
+        Opt::Render(render_opt) => render::command(render_opt),
+        Opt::Roundtrip(roundtrip_opt) => roundtrip::command(roundtrip_opt),
     }
 }
@@ -8,20 +8,20 @@ pub(crate) struct PasswordOpt {
 
 fn decode_char(c: char) -> Result<u8, Error> {
     if !c.is_ascii() {
-        return Err(format_err!("invalid character {}", c as char));
+        return Err(format_err!("invalid character {}", c));
     }
     let c = c as u8;
 
     // 0 - 25
-    if b'A' <= c && c <= b'Z' {
+    if c.is_ascii_uppercase() {
         return Ok(c - b'A');
     }
     // 26 - 34
-    if b'1' <= c && c <= b'9' {
+    if (b'1'..=b'9').contains(&c) {
         return Ok(c - b'1' + 26);
     }
     // 35 - 60
-    if b'a' <= c && c <= b'z' {
+    if c.is_ascii_lowercase() {
         return Ok(c - b'a' + 35);
     }
     if c == b'#' {
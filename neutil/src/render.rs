@@ -0,0 +1,75 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use failure::{format_err, Error};
+use structopt::StructOpt;
+
+use neutopia::{
+    render::{self, Image},
+    Neutopia,
+};
+
+/// Draw schematic PNGs of rooms and area maps with their objects and chests
+/// marked.  Tile graphics aren't decoded.
+#[derive(StructOpt, Debug)]
+pub(crate) struct RenderOpt {
+    #[structopt(long, parse(from_os_str), default_value = "Neutopia (USA).pce")]
+    rom: PathBuf,
+
+    /// Area to draw (hex).  Draws every area if not given.
+    #[structopt(long, parse(try_from_str = crate::parse_hex))]
+    area: Option<u32>,
+
+    /// Also draw this room of the area on its own (hex).
+    #[structopt(long, parse(try_from_str = crate::parse_hex))]
+    room: Option<u32>,
+
+    #[structopt(long, parse(from_os_str), default_value = "out")]
+    outdir: PathBuf,
+}
+
+fn write_png(path: &Path, image: &Image) -> Result<(), Error> {
+    let f = File::create(path)
+        .map_err(|e| format_err!("unable to create {}: {}", path.display(), e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(f), image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.to_rgb_bytes())?;
+    println!("wrote {}", path.display());
+    Ok(())
+}
+
+pub(crate) fn command(opt: &RenderOpt) -> Result<(), Error> {
+    let n = Neutopia::new(&crate::read_rom(&opt.rom)?)?;
+    fs::create_dir_all(&opt.outdir)?;
+
+    let areas: Vec<usize> = match opt.area.map(|area| area as usize) {
+        Some(area) if area < n.areas.len() => vec![area],
+        Some(area) => return Err(format_err!("no area {:02x}", area)),
+        None => (0..n.areas.len()).collect(),
+    };
+    if opt.room.is_some() && opt.area.is_none() {
+        return Err(format_err!("--room needs --area"));
+    }
+
+    for area_idx in areas {
+        let area = &n.areas[area_idx];
+        let path = opt.outdir.join(format!("area_{:02x}.png", area_idx));
+        write_png(&path, &render::render_area(area))?;
+
+        if let Some(room_idx) = opt.room.map(|room| room as usize) {
+            let room = area
+                .rooms
+                .get(room_idx)
+                .ok_or_else(|| format_err!("no room {:02x}", room_idx))?;
+            let path = opt
+                .outdir
+                .join(format!("area_{:02x}_room_{:02x}.png", area_idx, room_idx));
+            write_png(&path, &render::render_room(room))?;
+        }
+    }
+
+    Ok(())
+}
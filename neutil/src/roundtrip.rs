@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+use failure::{format_err, Error};
+use structopt::StructOpt;
+
+use neutopia::roundtrip;
+
+#[derive(StructOpt, Debug)]
+pub(crate) struct RoundtripOpt {
+    #[structopt(long, parse(from_os_str), default_value = "Neutopia (USA).pce")]
+    rom: PathBuf,
+}
+
+pub(crate) fn command(opt: &RoundtripOpt) -> Result<(), Error> {
+    let data = crate::read_rom(&opt.rom)?;
+
+    let diffs = roundtrip::round_trip(&data)?;
+    for diff in &diffs {
+        println!("{}", diff);
+    }
+    if !diffs.is_empty() {
+        return Err(format_err!(
+            "{} differences after writing {}",
+            diffs.len(),
+            opt.rom.display()
+        ));
+    }
+
+    println!("{} round trips without differences", opt.rom.display());
+    Ok(())
+}
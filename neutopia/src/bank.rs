@@ -0,0 +1,155 @@
+//! Mapping between HuC6280 addresses and ROM file offsets.
+//!
+//! The CPU sees a 64K logical address space split into eight 8K pages.
+//! Each page is mapped to a bank by one of the memory page registers (MPRs).
+//! Neutopia is a 384K HuCard which, like other cards of that size, is wired
+//! as a 256K and a 128K chip: banks $00-$3f map the first 256K (with
+//! $20-$3f mirroring $00-$1f) and banks $40-$7f map the last 128K (mirrored
+//! every $10 banks).
+//!
+//! Offsets here are into the ROM without its optional copier header.
+
+use failure::{format_err, Error};
+
+pub const BANK_SIZE: usize = 0x2000;
+pub const ROM_LEN: usize = 0x60000;
+pub const HEADER_LEN: usize = 0x200;
+
+const FIRST_CHIP_BANKS: u8 = 0x20;
+const SECOND_CHIP_BANKS: u8 = 0x10;
+const SECOND_CHIP_START: u8 = 0x40;
+const BANK_COUNT: u8 = 0x80;
+
+/// Returns the file offset of the start of `bank`.
+pub fn bank_offset(bank: u8) -> Result<usize, Error> {
+    let index = if bank < SECOND_CHIP_START {
+        bank % FIRST_CHIP_BANKS
+    } else if bank < BANK_COUNT {
+        FIRST_CHIP_BANKS + (bank - SECOND_CHIP_START) % SECOND_CHIP_BANKS
+    } else {
+        return Err(format_err!("bank {:02x} is not a ROM bank", bank));
+    };
+    Ok(index as usize * BANK_SIZE)
+}
+
+/// Returns the bank holding file offset `offset`.
+///
+/// The first 256K is returned as banks $00-$1f and the last 128K as banks
+/// $40-$4f.
+pub fn offset_bank(offset: usize) -> Result<u8, Error> {
+    if offset >= ROM_LEN {
+        return Err(format_err!(
+            "offset {:05x} is past the end of the ROM",
+            offset
+        ));
+    }
+    let index = (offset / BANK_SIZE) as u8;
+    Ok(if index < FIRST_CHIP_BANKS {
+        index
+    } else {
+        SECOND_CHIP_START + index - FIRST_CHIP_BANKS
+    })
+}
+
+/// Returns the offset of `offset` in a ROM that may have a header.
+pub fn file_offset(offset: usize, headered: bool) -> usize {
+    if headered {
+        offset + HEADER_LEN
+    } else {
+        offset
+    }
+}
+
+/// A logical address along with the bank mapped into its page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Address {
+    pub bank: u8,
+    pub address: u16,
+}
+
+impl Address {
+    pub fn new(bank: u8, address: u16) -> Self {
+        Address { bank, address }
+    }
+
+    /// Returns the location of `offset` when its bank is mapped into `page`.
+    pub fn from_offset(offset: usize, page: u8) -> Result<Self, Error> {
+        if page >= 8 {
+            return Err(format_err!("page {} out of range", page));
+        }
+        let bank = offset_bank(offset)?;
+        let address = ((page as usize * BANK_SIZE) | (offset % BANK_SIZE)) as u16;
+        Ok(Address { bank, address })
+    }
+
+    /// Index of the MPR that maps this address.
+    pub fn page(&self) -> u8 {
+        (self.address as usize / BANK_SIZE) as u8
+    }
+
+    pub fn offset(&self) -> Result<usize, Error> {
+        Ok(bank_offset(self.bank)? + self.address as usize % BANK_SIZE)
+    }
+}
+
+/// Contents of the eight memory page registers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mpr(pub [u8; 8]);
+
+impl Mpr {
+    /// Returns the bank and address `address` refers to.
+    pub fn resolve(&self, address: u16) -> Address {
+        Address::new(self.0[address as usize / BANK_SIZE], address)
+    }
+
+    /// Returns the file offset `address` refers to.
+    pub fn offset(&self, address: u16) -> Result<usize, Error> {
+        self.resolve(address)
+            .offset()
+            .map_err(|e| format_err!("address {:04x}: {}", address, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bank_offset() {
+        assert_eq!(bank_offset(0x00).unwrap(), 0x00000);
+        assert_eq!(bank_offset(0x03).unwrap(), 0x06000);
+        assert_eq!(bank_offset(0x23).unwrap(), 0x06000);
+        assert_eq!(bank_offset(0x40).unwrap(), 0x40000);
+        assert_eq!(bank_offset(0x48).unwrap(), 0x50000);
+        assert_eq!(bank_offset(0x58).unwrap(), 0x50000);
+        assert!(bank_offset(0x80).is_err());
+
+        assert_eq!(offset_bank(0x07df1).unwrap(), 0x03);
+        assert_eq!(offset_bank(0x5054e).unwrap(), 0x48);
+        assert!(offset_bank(ROM_LEN).is_err());
+
+        for bank in (0x00..0x20).chain(0x40..0x50) {
+            assert_eq!(offset_bank(bank_offset(bank).unwrap()).unwrap(), bank);
+        }
+    }
+
+    #[test]
+    fn test_address() {
+        // no-downgrade.asm's handler table.
+        let address = Address::new(0x03, 0xddf1);
+        assert_eq!(address.page(), 6);
+        assert_eq!(address.offset().unwrap(), 0x7df1);
+        assert_eq!(Address::from_offset(0x7df1, 6).unwrap(), address);
+        assert!(Address::from_offset(0x7df1, 8).is_err());
+
+        let mut mpr = Mpr([0xff, 0xf8, 0x48, 0x00, 0x00, 0x00, 0x03, 0x00]);
+        assert_eq!(mpr.offset(0xddf1).unwrap(), 0x7df1);
+        assert_eq!(mpr.offset(0x454e).unwrap(), 0x5054e);
+        assert!(mpr.offset(0x0000).is_err());
+        mpr.0[6] = 0x06;
+        assert_eq!(mpr.offset(0xd979).unwrap(), 0xd979);
+
+        assert_eq!(file_offset(0x7df1, true), 0x7ff1);
+        assert_eq!(file_offset(0x7df1, false), 0x7df1);
+    }
+}
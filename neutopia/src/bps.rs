@@ -0,0 +1,373 @@
+//! Reader and writer for the BPS patch format.
+//!
+//! BPS patches carry CRC32 checksums of the source, target, and patch
+//! itself so applying one to the wrong ROM is detected instead of producing
+//! a corrupt image.
+
+use std::convert::TryInto;
+
+use failure::{format_err, Error};
+
+const MAGIC: &[u8] = b"BPS1";
+const FOOTER_LEN: usize = 12;
+
+const SOURCE_READ: u64 = 0;
+const TARGET_READ: u64 = 1;
+const SOURCE_COPY: u64 = 2;
+const TARGET_COPY: u64 = 3;
+
+fn write_number(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let x = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(0x80 | x);
+            break;
+        }
+        out.push(x);
+        value -= 1;
+    }
+}
+
+fn write_action(out: &mut Vec<u8>, action: u64, len: usize) {
+    write_number(out, ((len as u64 - 1) << 2) | action);
+}
+
+/// Create a patch that turns `source` into `target`.
+pub fn create_patch(source: &[u8], target: &[u8], metadata: &str) -> Vec<u8> {
+    let mut patch = MAGIC.to_vec();
+    write_number(&mut patch, source.len() as u64);
+    write_number(&mut patch, target.len() as u64);
+    write_number(&mut patch, metadata.len() as u64);
+    patch.extend_from_slice(metadata.as_bytes());
+
+    let same = |i: usize| i < source.len() && source[i] == target[i];
+
+    let mut i = 0;
+    while i < target.len() {
+        let start = i;
+        if same(i) {
+            while i < target.len() && same(i) {
+                i += 1;
+            }
+            write_action(&mut patch, SOURCE_READ, i - start);
+        } else {
+            while i < target.len() && !same(i) {
+                i += 1;
+            }
+            write_action(&mut patch, TARGET_READ, i - start);
+            patch.extend_from_slice(&target[start..i]);
+        }
+    }
+
+    patch.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+    patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+    let patch_crc = crc32fast::hash(&patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+
+    patch
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        let b = *self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| format_err!("unexpected end of patch at {:x}", self.pos))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| format_err!("unexpected end of patch at {:x}", self.pos))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_number(&mut self) -> Result<u64, Error> {
+        let mut data: u64 = 0;
+        let mut shift: u64 = 1;
+        loop {
+            let x = self.read_u8()?;
+            data = (x as u64 & 0x7f)
+                .checked_mul(shift)
+                .and_then(|v| v.checked_add(data))
+                .ok_or_else(|| format_err!("number overflow at {:x}", self.pos))?;
+            if x & 0x80 != 0 {
+                break;
+            }
+            shift = shift
+                .checked_shl(7)
+                .filter(|s| *s < (1 << 56))
+                .ok_or_else(|| format_err!("number overflow at {:x}", self.pos))?;
+            data = data
+                .checked_add(shift)
+                .ok_or_else(|| format_err!("number overflow at {:x}", self.pos))?;
+        }
+        Ok(data)
+    }
+
+    fn read_offset(&mut self) -> Result<i64, Error> {
+        let value = self.read_number()?;
+        let magnitude = (value >> 1) as i64;
+        Ok(if value & 1 != 0 {
+            -magnitude
+        } else {
+            magnitude
+        })
+    }
+}
+
+fn read_crc(data: &[u8]) -> u32 {
+    u32::from_le_bytes(data[..4].try_into().unwrap())
+}
+
+fn apply_offset(base: usize, offset: i64, limit: usize) -> Result<usize, Error> {
+    let value = base as i64 + offset;
+    if value < 0 || value as usize > limit {
+        return Err(format_err!(
+            "copy offset {} from {:x} out of range",
+            offset,
+            base
+        ));
+    }
+    Ok(value as usize)
+}
+
+/// Information stored in a patch's header and footer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PatchInfo {
+    pub source_size: usize,
+    pub target_size: usize,
+    pub source_crc: u32,
+    pub target_crc: u32,
+    pub metadata: String,
+}
+
+/// Read a patch's header and footer, validating its own checksum.
+pub fn patch_info(patch: &[u8]) -> Result<PatchInfo, Error> {
+    if patch.len() < MAGIC.len() + FOOTER_LEN || !patch.starts_with(MAGIC) {
+        return Err(format_err!("not a BPS patch"));
+    }
+
+    let footer = &patch[patch.len() - FOOTER_LEN..];
+    let patch_crc = crc32fast::hash(&patch[..patch.len() - 4]);
+    if patch_crc != read_crc(&footer[8..]) {
+        return Err(format_err!(
+            "patch checksum {:08x} does not match expected {:08x}",
+            patch_crc,
+            read_crc(&footer[8..])
+        ));
+    }
+
+    let mut r = Reader {
+        data: &patch[..patch.len() - FOOTER_LEN],
+        pos: MAGIC.len(),
+    };
+    let source_size = r.read_number()? as usize;
+    let target_size = r.read_number()? as usize;
+    let metadata_len = r.read_number()? as usize;
+    let metadata = String::from_utf8_lossy(r.read_bytes(metadata_len)?).to_string();
+
+    Ok(PatchInfo {
+        source_size,
+        target_size,
+        source_crc: read_crc(&footer[0..]),
+        target_crc: read_crc(&footer[4..]),
+        metadata,
+    })
+}
+
+/// Apply `patch` to `source` returning the target.
+///
+/// Fails if `source` or the resulting target don't match the checksums
+/// recorded in the patch.
+pub fn apply_patch(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    let info = patch_info(patch)?;
+
+    if source.len() != info.source_size {
+        return Err(format_err!(
+            "source is {} bytes, patch expects {}",
+            source.len(),
+            info.source_size
+        ));
+    }
+    let source_crc = crc32fast::hash(source);
+    if source_crc != info.source_crc {
+        return Err(format_err!(
+            "source checksum {:08x} does not match expected {:08x}",
+            source_crc,
+            info.source_crc
+        ));
+    }
+
+    let mut r = Reader {
+        data: &patch[..patch.len() - FOOTER_LEN],
+        pos: MAGIC.len(),
+    };
+    r.read_number()?;
+    r.read_number()?;
+    let metadata_len = r.read_number()? as usize;
+    r.read_bytes(metadata_len)?;
+
+    let mut target: Vec<u8> = Vec::with_capacity(info.target_size);
+    let mut source_offset = 0;
+    let mut target_offset = 0;
+    while r.pos < r.data.len() {
+        let data = r.read_number()?;
+        let action = data & 3;
+        let len = (data >> 2) as usize + 1;
+        if target.len() + len > info.target_size {
+            return Err(format_err!(
+                "patch writes past the end of the {} byte target",
+                info.target_size
+            ));
+        }
+
+        match action {
+            SOURCE_READ => {
+                let start = target.len();
+                let bytes = source
+                    .get(start..start + len)
+                    .ok_or_else(|| format_err!("source read at {:x} out of range", start))?;
+                target.extend_from_slice(bytes);
+            }
+            TARGET_READ => target.extend_from_slice(r.read_bytes(len)?),
+            SOURCE_COPY => {
+                source_offset = apply_offset(source_offset, r.read_offset()?, source.len())?;
+                let bytes = source
+                    .get(source_offset..source_offset + len)
+                    .ok_or_else(|| {
+                        format_err!("source copy at {:x} out of range", source_offset)
+                    })?;
+                target.extend_from_slice(bytes);
+                source_offset += len;
+            }
+            TARGET_COPY => {
+                target_offset = apply_offset(target_offset, r.read_offset()?, target.len())?;
+                if target_offset >= target.len() {
+                    return Err(format_err!(
+                        "target copy at {:x} out of range",
+                        target_offset
+                    ));
+                }
+                // Target copies may overlap the bytes they produce so copy
+                // a byte at a time.
+                for _ in 0..len {
+                    let b = target[target_offset];
+                    target.push(b);
+                    target_offset += 1;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if target.len() != info.target_size {
+        return Err(format_err!(
+            "patch produced {} bytes, expected {}",
+            target.len(),
+            info.target_size
+        ));
+    }
+    let target_crc = crc32fast::hash(&target);
+    if target_crc != info.target_crc {
+        return Err(format_err!(
+            "target checksum {:08x} does not match expected {:08x}",
+            target_crc,
+            info.target_crc
+        ));
+    }
+
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_round_trip() {
+        for value in &[0u64, 1, 0x7f, 0x80, 0x3fff, 0x4000, 0x123456, 0xffff_ffff] {
+            let mut data = Vec::new();
+            write_number(&mut data, *value);
+            let mut r = Reader {
+                data: &data,
+                pos: 0,
+            };
+            assert_eq!(r.read_number().unwrap(), *value);
+            assert_eq!(r.pos, data.len());
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let source: Vec<u8> = (0..1024).map(|i| i as u8).collect();
+        let mut target = source.clone();
+        target[10] = 0xff;
+        target[11] = 0xfe;
+        target[500..520].copy_from_slice(&[0x55; 20]);
+        target.extend_from_slice(b"extra");
+
+        let patch = create_patch(&source, &target, "test");
+        let info = patch_info(&patch).unwrap();
+        assert_eq!(info.source_size, source.len());
+        assert_eq!(info.target_size, target.len());
+        assert_eq!(info.metadata, "test");
+
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn test_checksums() {
+        let source = vec![0u8; 64];
+        let mut target = source.clone();
+        target[3] = 1;
+        let patch = create_patch(&source, &target, "");
+
+        let mut wrong_source = source.clone();
+        wrong_source[0] = 1;
+        assert!(apply_patch(&wrong_source, &patch).is_err());
+
+        let mut corrupt = patch.clone();
+        let len = corrupt.len();
+        corrupt[len - FOOTER_LEN - 1] ^= 0xff;
+        assert!(apply_patch(&source, &corrupt).is_err());
+    }
+
+    #[test]
+    fn test_copy_actions() {
+        let source = b"abcdef".to_vec();
+        let target = b"cdefababab".to_vec();
+
+        let mut patch = MAGIC.to_vec();
+        write_number(&mut patch, source.len() as u64);
+        write_number(&mut patch, target.len() as u64);
+        write_number(&mut patch, 0);
+        // "cdef" copied from source offset 2.
+        write_action(&mut patch, SOURCE_COPY, 4);
+        write_number(&mut patch, 2 << 1);
+        // "ab" copied from source offset 0 (relative -6).
+        write_action(&mut patch, SOURCE_COPY, 2);
+        write_number(&mut patch, (6 << 1) | 1);
+        // "abab" copied from target offset 4, overlapping its own output.
+        write_action(&mut patch, TARGET_COPY, 4);
+        write_number(&mut patch, 4 << 1);
+        patch.extend_from_slice(&crc32fast::hash(&source).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(&target).to_le_bytes());
+        let crc = crc32fast::hash(&patch);
+        patch.extend_from_slice(&crc.to_le_bytes());
+
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+    }
+}
@@ -0,0 +1,359 @@
+//! Structural comparison of two ROMs.
+//!
+//! Chests, warps, enemies, and object table entries are compared through
+//! the [`Neutopia`] model so relocated tables don't show up as changes.
+//! Bytes outside the tables either ROM parsed are compared directly, which
+//! picks up code and text patches.
+
+use std::fmt;
+
+use super::{
+    area_name,
+    interval::{Interval, IntervalStore},
+    rom::{self, object::TableEntry},
+    Neutopia,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Chest {
+        index: usize,
+        a: rom::Chest,
+        b: rom::Chest,
+    },
+    Warps {
+        a: Vec<u8>,
+        b: Vec<u8>,
+    },
+    Enemies {
+        a: Vec<u8>,
+        b: Vec<u8>,
+    },
+    /// An entry that differs, or is only present in one ROM.
+    Object {
+        index: usize,
+        a: Option<TableEntry>,
+        b: Option<TableEntry>,
+    },
+}
+
+fn chest_text(chest: &rom::Chest) -> String {
+    format!(
+        "{} [{:02x} {:02x} {:02x} {:02x}]",
+        chest.item(),
+        chest.item_id,
+        chest.arg,
+        chest.text,
+        chest.unknown
+    )
+}
+
+fn entry_text(entry: &Option<TableEntry>) -> String {
+    match entry {
+        Some(entry) => entry.to_string(),
+        None => "nothing".to_string(),
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Chest { index, a, b } => {
+                write!(f, "chest {}: {} -> {}", index, chest_text(a), chest_text(b))
+            }
+            Self::Warps { a, b } => write!(f, "warps: {:02x?} -> {:02x?}", a, b),
+            Self::Enemies { a, b } => write!(f, "enemies: {:02x?} -> {:02x?}", a, b),
+            Self::Object { index, a, b } => write!(
+                f,
+                "object {}: {} -> {}",
+                index,
+                entry_text(a),
+                entry_text(b)
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoomDiff {
+    pub room: u8,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AreaDiff {
+    pub area: u8,
+    /// Changes to the area's chest table.
+    pub chests: Vec<Change>,
+    pub rooms: Vec<RoomDiff>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diff {
+    /// Areas with at least one change.
+    pub areas: Vec<AreaDiff>,
+    /// Ranges of differing bytes outside the parsed tables.
+    pub bytes: Vec<Interval<usize>>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.areas.is_empty() && self.bytes.is_empty()
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for area in &self.areas {
+            writeln!(f, "{} (area {:02x})", area_name(area.area), area.area)?;
+            for change in &area.chests {
+                writeln!(f, "  {}", change)?;
+            }
+            for room in &area.rooms {
+                writeln!(f, "  room {:02x}", room.room)?;
+                for change in &room.changes {
+                    writeln!(f, "    {}", change)?;
+                }
+            }
+        }
+        if !self.bytes.is_empty() {
+            writeln!(f, "bytes outside known tables")?;
+            for range in &self.bytes {
+                writeln!(
+                    f,
+                    "  {:05x}-{:05x} ({} bytes)",
+                    range.start,
+                    range.end,
+                    range.end - range.start
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn diff_room(a: &super::Room, b: &super::Room) -> Vec<Change> {
+    let mut changes = Vec::new();
+    if a.warps != b.warps {
+        changes.push(Change::Warps {
+            a: a.warps.clone(),
+            b: b.warps.clone(),
+        });
+    }
+    if a.enemies != b.enemies {
+        changes.push(Change::Enemies {
+            a: a.enemies.clone(),
+            b: b.enemies.clone(),
+        });
+    }
+    for index in 0..a.objects.len().max(b.objects.len()) {
+        let (a, b) = (a.objects.get(index), b.objects.get(index));
+        if a != b {
+            changes.push(Change::Object {
+                index,
+                a: a.cloned(),
+                b: b.cloned(),
+            });
+        }
+    }
+    changes
+}
+
+// Returns the ranges where `a` and `b` differ that aren't in `known`.  Any
+// bytes past the end of the shorter ROM count as different.
+fn diff_bytes(a: &[u8], b: &[u8], known: &IntervalStore<usize>) -> Vec<Interval<usize>> {
+    let known = known.get_intervals();
+    let mut known = known.iter().peekable();
+    let mut ranges: Vec<Interval<usize>> = Vec::new();
+
+    for offset in 0..a.len().max(b.len()) {
+        while known.peek().is_some_and(|i| i.end <= offset) {
+            known.next();
+        }
+        if known.peek().is_some_and(|i| i.start <= offset) || a.get(offset) == b.get(offset) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(range) if range.end == offset => range.end += 1,
+            _ => ranges.push(Interval {
+                start: offset,
+                end: offset + 1,
+            }),
+        }
+    }
+
+    ranges
+}
+
+/// Compare `a` with `b`, grouping the changes by area and room.
+pub fn diff(a: &Neutopia, b: &Neutopia) -> Diff {
+    let mut areas = Vec::new();
+    for (area_idx, (area_a, area_b)) in a.areas.iter().zip(&b.areas).enumerate() {
+        let chests = area_a
+            .chest_table
+            .iter()
+            .zip(&area_b.chest_table)
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(index, (a, b))| Change::Chest {
+                index,
+                a: a.clone(),
+                b: b.clone(),
+            })
+            .collect();
+        let rooms: Vec<RoomDiff> = area_a
+            .rooms
+            .iter()
+            .zip(&area_b.rooms)
+            .enumerate()
+            .map(|(room, (a, b))| RoomDiff {
+                room: room as u8,
+                changes: diff_room(a, b),
+            })
+            .filter(|r| !r.changes.is_empty())
+            .collect();
+
+        let area = AreaDiff {
+            area: area_idx as u8,
+            chests,
+            rooms,
+        };
+        if !area.chests.is_empty() || !area.rooms.is_empty() {
+            areas.push(area);
+        }
+    }
+
+    let mut known = a.n.data_intervals();
+    for interval in b.n.data_intervals().get_intervals() {
+        known.add(interval.start, interval.end);
+    }
+
+    Diff {
+        areas,
+        bytes: diff_bytes(&a.rom_data, &b.rom_data, &known),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom::object::ObjectInfo;
+    use crate::synthetic::{RomBuilder, RoomSpec};
+
+    fn object(x: u8, y: u8, id: u8) -> TableEntry {
+        TableEntry::Object(ObjectInfo { x, y, id })
+    }
+
+    fn chest(item_id: u8) -> rom::Chest {
+        rom::Chest {
+            item_id,
+            arg: 1,
+            text: 0x85,
+            unknown: 0x41,
+        }
+    }
+
+    fn builder() -> RomBuilder {
+        RomBuilder::new()
+            .room(
+                4,
+                0x12,
+                RoomSpec {
+                    warps: vec![0x01, 0x02],
+                    enemies: vec![0x10],
+                    objects: vec![object(2, 5, 0x4c), object(3, 5, 0x4d)],
+                },
+            )
+            .chest(4, 0, chest(0x16))
+            .chest(4, 1, chest(0x17))
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let data = builder().build().unwrap();
+        let a = Neutopia::new(&data).unwrap();
+        assert!(diff(&a, &a).is_empty());
+
+        // Writing relocates tables, which isn't a change.
+        let b = Neutopia::new(&a.write().unwrap()).unwrap();
+        assert_eq!(diff(&a, &b), diff(&a, &a));
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = Neutopia::new(&builder().build().unwrap()).unwrap();
+
+        let mut b = builder()
+            .room(
+                4,
+                0x12,
+                RoomSpec {
+                    warps: vec![0x01, 0x03],
+                    enemies: vec![0x10],
+                    objects: vec![object(2, 5, 0x4c)],
+                },
+            )
+            .chest(4, 0, chest(0x17))
+            .chest(4, 1, chest(0x16))
+            .build()
+            .unwrap();
+        b[0x100..0x104].copy_from_slice(&[1, 2, 0, 3]);
+        let b = Neutopia::new(&b).unwrap();
+
+        let d = diff(&a, &b);
+        assert_eq!(d.areas.len(), 1);
+        assert_eq!(d.areas[0].area, 4);
+        assert_eq!(d.areas[0].chests.len(), 2);
+        assert_eq!(
+            d.areas[0].rooms[0].changes,
+            vec![
+                Change::Warps {
+                    a: vec![0x01, 0x02],
+                    b: vec![0x01, 0x03]
+                },
+                Change::Object {
+                    index: 1,
+                    a: Some(object(3, 5, 0x4d)),
+                    b: None
+                },
+            ]
+        );
+        assert_eq!(
+            d.bytes,
+            vec![
+                Interval {
+                    start: 0x100,
+                    end: 0x102
+                },
+                Interval {
+                    start: 0x103,
+                    end: 0x104
+                },
+            ]
+        );
+
+        assert_eq!(
+            d.to_string(),
+            "Crypt 1 (area 04)\n\
+             \x20 chest 0: Crypt 5 Medallion [16 01 85 41] -> Crypt 6 Medallion [17 01 85 41]\n\
+             \x20 chest 1: Crypt 6 Medallion [17 01 85 41] -> Crypt 5 Medallion [16 01 85 41]\n\
+             \x20 room 12\n\
+             \x20   warps: [01, 02] -> [01, 03]\n\
+             \x20   object 1: object 0x4d @ (3,5) -> nothing\n\
+             bytes outside known tables\n\
+             \x20 00100-00102 (2 bytes)\n\
+             \x20 00103-00104 (1 bytes)\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_bytes() {
+        let mut known = IntervalStore::new();
+        known.add(2, 4);
+        let ranges = diff_bytes(&[0, 0, 0, 0, 0], &[1, 1, 1, 1, 0, 1], &known);
+        assert_eq!(
+            ranges,
+            vec![Interval { start: 0, end: 2 }, Interval { start: 5, end: 6 }]
+        );
+    }
+}
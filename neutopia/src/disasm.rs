@@ -0,0 +1,388 @@
+//! HuC6280 disassembler.
+//!
+//! Output uses the same syntax as the patch sources in `rando/src/asm` so
+//! listings can be pasted back into a patch.  Ranges known to hold data
+//! (see [`data_regions`]) are listed as `db`s instead of being decoded.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::{
+    huc6280::{self, Mode, Opcode},
+    interval::{Interval, IntervalStore},
+    rommap, NeutopiaRom,
+};
+
+/// Label names by CPU address.
+pub type Labels = BTreeMap<u32, String>;
+
+/// Number of data bytes listed per `db`.
+const DATA_LINE_LEN: usize = 8;
+
+/// Returns the file offsets known to hold data rather than code: every table
+/// parsed by [`NeutopiaRom`] and the randomizer's info block.
+pub fn data_regions(rom: &NeutopiaRom) -> IntervalStore<usize> {
+    let mut regions = rom.data_intervals();
+    regions.add(
+        rommap::RANDO_INFO,
+        rommap::RANDO_INFO + rommap::RANDO_INFO_LEN,
+    );
+    regions
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    /// File offset of the first byte.
+    pub offset: usize,
+    /// CPU address of the first byte.
+    pub address: u32,
+    pub bytes: Vec<u8>,
+    /// `None` if the bytes are not a valid instruction and are shown as
+    /// `db`.
+    pub opcode: Option<&'static Opcode>,
+}
+
+fn hex8(v: u8) -> String {
+    format!("${:02x}", v)
+}
+
+fn addr16(v: u16, labels: &Labels) -> String {
+    match labels.get(&(v as u32)) {
+        Some(label) => label.clone(),
+        None => format!("${:04x}", v),
+    }
+}
+
+impl Instruction {
+    fn word(&self, i: usize) -> u16 {
+        u16::from_le_bytes([self.bytes[i], self.bytes[i + 1]])
+    }
+
+    // Target of a branch whose signed offset is at `i`.
+    fn branch_target(&self, i: usize) -> u16 {
+        let next = self.address as i64 + self.bytes.len() as i64;
+        (next + self.bytes[i] as i8 as i64) as u16
+    }
+
+    /// Returns the assembly text of the instruction, using names from
+    /// `labels` for addresses.
+    pub fn text(&self, labels: &Labels) -> String {
+        let op = match self.opcode {
+            Some(op) => op,
+            None => {
+                let bytes: Vec<String> = self.bytes.iter().map(|b| hex8(*b)).collect();
+                return format!("db {}", bytes.join(", "));
+            }
+        };
+
+        let b = &self.bytes;
+        let operand = match op.mode {
+            Mode::Implied => String::new(),
+            Mode::Immediate => format!("#{}", hex8(b[1])),
+            Mode::ZeroPage => hex8(b[1]),
+            Mode::ZeroPageX => format!("{},x", hex8(b[1])),
+            Mode::ZeroPageY => format!("{},y", hex8(b[1])),
+            Mode::ZeroPageIndirect => format!("({})", hex8(b[1])),
+            Mode::ZeroPageIndirectX => format!("({},x)", hex8(b[1])),
+            Mode::ZeroPageIndirectY => format!("({}),y", hex8(b[1])),
+            Mode::Absolute => addr16(self.word(1), labels),
+            Mode::AbsoluteX => format!("{},x", addr16(self.word(1), labels)),
+            Mode::AbsoluteY => format!("{},y", addr16(self.word(1), labels)),
+            Mode::AbsoluteIndirect => format!("({})", addr16(self.word(1), labels)),
+            Mode::AbsoluteIndirectX => format!("({},x)", addr16(self.word(1), labels)),
+            Mode::Relative => addr16(self.branch_target(1), labels),
+            Mode::ZeroPageRelative => {
+                format!("{}, {}", hex8(b[1]), addr16(self.branch_target(2), labels))
+            }
+            Mode::ImmediateZeroPage => format!("#{}, {}", hex8(b[1]), hex8(b[2])),
+            Mode::ImmediateZeroPageX => format!("#{}, {},x", hex8(b[1]), hex8(b[2])),
+            Mode::ImmediateAbsolute => {
+                format!("#{}, {}", hex8(b[1]), addr16(self.word(2), labels))
+            }
+            Mode::ImmediateAbsoluteX => {
+                format!("#{}, {},x", hex8(b[1]), addr16(self.word(2), labels))
+            }
+            Mode::Block => format!(
+                "{}, {}, ${:04x}",
+                addr16(self.word(1), labels),
+                addr16(self.word(3), labels),
+                self.word(5)
+            ),
+        };
+
+        if operand.is_empty() {
+            op.mnemonic.to_string()
+        } else {
+            format!("{} {}", op.mnemonic, operand)
+        }
+    }
+
+    /// Returns a listing line with the offset, address, and bytes of the
+    /// instruction.
+    pub fn listing(&self, labels: &Labels) -> String {
+        let mut bytes = String::new();
+        for b in &self.bytes {
+            write!(bytes, "{:02x} ", b).unwrap();
+        }
+        format!(
+            "{:05x} {:04x}: {:21} {}",
+            self.offset,
+            self.address,
+            bytes,
+            self.text(labels)
+        )
+    }
+}
+
+/// Disassemble `len` bytes of `data` starting at file offset `offset`,
+/// which the CPU sees at `address`.
+///
+/// Stops early at the end of `data`.  Bytes in `data_regions` are returned
+/// as `db`s of up to eight bytes, as are single bytes that don't decode to
+/// an instruction.  Instructions never run into a data region.
+pub fn disassemble(
+    data: &[u8],
+    offset: usize,
+    address: u32,
+    len: usize,
+    data_regions: &IntervalStore<usize>,
+) -> Vec<Instruction> {
+    let end = offset.saturating_add(len).min(data.len());
+    let regions: Vec<Interval<usize>> = data_regions
+        .get_intervals()
+        .into_iter()
+        .filter(|i| i.end > offset && i.start < end)
+        .collect();
+    let mut instructions = Vec::new();
+    let mut pos = offset;
+
+    while pos < end {
+        let address = address + (pos - offset) as u32;
+        let (len, opcode) = match regions.iter().find(|i| i.start <= pos && pos < i.end) {
+            Some(region) => ((region.end.min(end) - pos).min(DATA_LINE_LEN), None),
+            None => {
+                // Code stops at the next data region as well as at `end`.
+                let code_end = regions
+                    .iter()
+                    .map(|i| i.start)
+                    .filter(|start| *start > pos)
+                    .fold(end, usize::min);
+                let opcode =
+                    huc6280::decode(data[pos]).filter(|op| pos + op.instruction_len() <= code_end);
+                (opcode.map_or(1, |op| op.instruction_len()), opcode)
+            }
+        };
+        instructions.push(Instruction {
+            offset: pos,
+            address,
+            bytes: data[pos..pos + len].to_vec(),
+            opcode,
+        });
+        pos += len;
+    }
+
+    instructions
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Removed(Instruction),
+    Added(Instruction),
+}
+
+/// Compare the instructions in the same range of `vanilla` and `patched`,
+/// each disassembled with its own data regions.
+///
+/// Returns groups of consecutive changes.  Instructions that are identical
+/// in both are omitted.
+pub fn diff(
+    vanilla: (&[u8], &IntervalStore<usize>),
+    patched: (&[u8], &IntervalStore<usize>),
+    offset: usize,
+    address: u32,
+    len: usize,
+) -> Vec<Vec<Change>> {
+    let a = disassemble(vanilla.0, offset, address, len, vanilla.1);
+    let b = disassemble(patched.0, offset, address, len, patched.1);
+
+    let mut groups = Vec::new();
+    let mut group = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() || j < b.len() {
+        match (a.get(i), b.get(j)) {
+            (Some(x), Some(y)) if x.address == y.address && x.bytes == y.bytes => {
+                if !group.is_empty() {
+                    groups.push(group);
+                    group = Vec::new();
+                }
+                i += 1;
+                j += 1;
+            }
+            (Some(x), Some(y)) if x.address == y.address => {
+                group.push(Change::Removed(x.clone()));
+                group.push(Change::Added(y.clone()));
+                i += 1;
+                j += 1;
+            }
+            (Some(x), Some(y)) if x.address < y.address => {
+                group.push(Change::Removed(x.clone()));
+                i += 1;
+            }
+            (Some(x), None) => {
+                group.push(Change::Removed(x.clone()));
+                i += 1;
+            }
+            (_, Some(y)) => {
+                group.push(Change::Added(y.clone()));
+                j += 1;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    if !group.is_empty() {
+        groups.push(group);
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::RomBuilder;
+
+    fn code(data: &[u8], address: u32) -> Vec<Instruction> {
+        disassemble(data, 0, address, data.len(), &IntervalStore::new())
+    }
+
+    fn texts(instructions: &[Instruction], labels: &Labels) -> Vec<String> {
+        instructions.iter().map(|i| i.text(labels)).collect()
+    }
+
+    #[test]
+    fn test_disassemble() {
+        // From rando/src/asm/no-downgrade.asm.
+        let data = [
+            0xae, 0xd0, 0x35, 0xad, 0xd1, 0x35, 0xdd, 0x44, 0x2e, 0x90, 0x03, 0x9d, 0x44, 0x2e,
+            0xbd, 0x44, 0x2e, 0x8d, 0xd1, 0x35, 0x4c, 0x79, 0xde,
+        ];
+        let mut labels = Labels::new();
+        labels.insert(0xdf60, "skip_write".to_string());
+
+        let instructions = code(&data, 0xdf52);
+        assert_eq!(
+            texts(&instructions, &labels),
+            vec![
+                "ldx $35d0",
+                "lda $35d1",
+                "cmp $2e44,x",
+                "bcc skip_write",
+                "sta $2e44,x",
+                "lda $2e44,x",
+                "sta $35d1",
+                "jmp $de79",
+            ]
+        );
+        assert_eq!(instructions[3].address, 0xdf5b);
+        assert_eq!(
+            instructions[0].listing(&labels),
+            "00000 df52: ae d0 35              ldx $35d0"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_modes() {
+        let data = [
+            0x62, 0xa9, 0x12, 0xb1, 0x34, 0x7c, 0x00, 0x80, 0x0f, 0x12, 0xfd, 0x73, 0x00, 0x10,
+            0x00, 0x20, 0x10, 0x00, 0xb3, 0x01, 0x34, 0x12, 0x02,
+        ];
+        let instructions = code(&data, 0x8000);
+        assert_eq!(
+            texts(&instructions, &Labels::new()),
+            vec![
+                "cla",
+                "lda #$12",
+                "lda ($34),y",
+                "jmp ($8000,x)",
+                "bbr0 $12, $8008",
+                "tii $1000, $2000, $0010",
+                "tst #$01, $1234,x",
+                "sxy",
+            ]
+        );
+
+        // Invalid opcodes and truncated instructions become db.
+        let instructions = code(&[0x0b, 0xad, 0x12], 0);
+        assert_eq!(
+            texts(&instructions, &Labels::new()),
+            vec!["db $0b", "db $ad", "db $12"]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_data_regions() {
+        // A jmp cut short by a table, which runs past the end of a line.
+        let mut data = vec![0xea, 0x4c, 0x00];
+        data.extend_from_slice(&[0xa9; 10]);
+        data.push(0x60);
+        let mut regions = IntervalStore::new();
+        regions.add(2, 13);
+
+        let instructions = disassemble(&data, 0, 0x8000, data.len(), &regions);
+        assert_eq!(
+            texts(&instructions, &Labels::new()),
+            vec![
+                "nop",
+                "db $4c",
+                "db $00, $a9, $a9, $a9, $a9, $a9, $a9, $a9",
+                "db $a9, $a9, $a9",
+                "rts",
+            ]
+        );
+        assert_eq!(instructions[3].address, 0x800a);
+
+        // Regions outside the range don't matter.
+        let instructions = disassemble(&data, 13, 0x800d, 1, &regions);
+        assert_eq!(texts(&instructions, &Labels::new()), vec!["rts"]);
+    }
+
+    #[test]
+    fn test_data_regions() {
+        let data = RomBuilder::new().build().unwrap();
+        let regions = data_regions(&NeutopiaRom::new(&data).unwrap());
+        assert!(regions.covers(rommap::AREA_TABLE, rommap::AREA_TABLE + 3));
+        assert!(regions.covers(
+            rommap::RANDO_INFO,
+            rommap::RANDO_INFO + rommap::RANDO_INFO_LEN
+        ));
+        assert!(!regions.covers(0, 1));
+    }
+
+    #[test]
+    fn test_diff() {
+        // intro-skip-like byte change and an instruction that changes length.
+        let vanilla = [0xea, 0xa9, 0x01, 0xea, 0xad, 0x00, 0x20, 0xea];
+        let patched = [0xea, 0xa9, 0x02, 0xea, 0x62, 0xea, 0xea, 0xea];
+        let none = IntervalStore::new();
+        let groups = diff(
+            (&vanilla, &none),
+            (&patched, &none),
+            0,
+            0x8000,
+            vanilla.len(),
+        );
+        assert_eq!(groups.len(), 2);
+
+        let text = |c: &Change| match c {
+            Change::Removed(i) => format!("-{}", i.text(&Labels::new())),
+            Change::Added(i) => format!("+{}", i.text(&Labels::new())),
+        };
+        let groups: Vec<Vec<String>> = groups
+            .iter()
+            .map(|g| g.iter().map(text).collect())
+            .collect();
+        assert_eq!(groups[0], vec!["-lda #$01", "+lda #$02"]);
+        assert_eq!(groups[1], vec!["-lda $2000", "+cla", "+nop", "+nop"]);
+    }
+}
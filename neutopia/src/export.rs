@@ -0,0 +1,219 @@
+//! Serializable snapshot of the game data in a [`Neutopia`].
+//!
+//! [`GameData`] holds everything [`Neutopia::write`] writes back to the ROM
+//! so it can be dumped to a text format, edited, and imported again.
+
+use std::collections::HashMap;
+
+use failure::{format_err, Error};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    rom::{self, object::TableEntry},
+    validate, Area, Conditional, Neutopia, REWRITTEN_AREAS,
+};
+
+/// Areas in [`GameData`], not counting area 10, which shares area c's rooms.
+pub const AREA_COUNT: usize = 0x10;
+/// Rooms and room order entries in each area.
+pub const ROOM_COUNT: usize = 0x40;
+
+/// Entries that follow a chest's object in its room's object table.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ChestConditional {
+    pub chest: rom::Chest,
+    pub data: Vec<TableEntry>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GameData {
+    pub areas: Vec<Area>,
+    /// Sorted by chest.
+    pub conditionals: Vec<ChestConditional>,
+}
+
+fn check_area(area_idx: usize, area: &Area) -> Result<(), Error> {
+    let counts = [
+        ("rooms", area.rooms.len(), ROOM_COUNT),
+        ("room order entries", area.room_order.len(), ROOM_COUNT),
+        ("chests", area.chest_table.len(), rom::CHEST_TABLE_LEN),
+    ];
+    for (what, len, expected) in &counts {
+        if len != expected {
+            return Err(format_err!(
+                "area {:02x} has {} {}, need {}",
+                area_idx,
+                len,
+                what,
+                expected
+            ));
+        }
+    }
+
+    if let Some(error) = validate::validate_area(area_idx as u8, area)
+        .into_iter()
+        .next()
+    {
+        return Err(error.into());
+    }
+
+    Ok(())
+}
+
+impl Neutopia {
+    /// Returns a copy of the game data.
+    pub fn export(&self) -> GameData {
+        let mut conditionals: Vec<ChestConditional> = self
+            .conditionals
+            .iter()
+            .map(|(chest, c)| ChestConditional {
+                chest: chest.clone(),
+                data: c.data.clone(),
+            })
+            .collect();
+        conditionals.sort_by(|a, b| a.chest.cmp(&b.chest));
+
+        GameData {
+            areas: self.areas.clone(),
+            conditionals,
+        }
+    }
+
+    /// Replace the game data with `data`.
+    ///
+    /// The data is checked against the layout [`Neutopia::write`] can
+    /// produce.  Rooms and chests in areas 0-3 are not rewritten so they
+    /// must be unchanged.
+    pub fn import(&mut self, data: GameData) -> Result<(), Error> {
+        if data.areas.len() != AREA_COUNT {
+            return Err(format_err!(
+                "{} areas given, need {}",
+                data.areas.len(),
+                AREA_COUNT
+            ));
+        }
+        for (area_idx, area) in data.areas.iter().enumerate() {
+            check_area(area_idx, area)?;
+            if REWRITTEN_AREAS.contains(&area_idx) {
+                continue;
+            }
+            let current = &self.areas[area_idx];
+            let rooms_match = area.rooms.iter().zip(&current.rooms).all(|(a, b)| {
+                a.warps == b.warps && a.enemies == b.enemies && a.objects == b.objects
+            });
+            if !rooms_match || area.chest_table != current.chest_table {
+                return Err(format_err!(
+                    "area {:02x} rooms and chests can't be changed",
+                    area_idx
+                ));
+            }
+        }
+
+        let mut conditionals = HashMap::new();
+        for c in data.conditionals {
+            let chest = c.chest.clone();
+            if conditionals
+                .insert(c.chest, Conditional { data: c.data })
+                .is_some()
+            {
+                return Err(format_err!("duplicate conditional for {:x?}", chest));
+            }
+        }
+
+        self.areas = data.areas;
+        self.conditionals = conditionals;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rom::object::ObjectInfo,
+        roundtrip,
+        synthetic::{RomBuilder, RoomSpec},
+    };
+
+    fn object(x: u8, y: u8, id: u8) -> TableEntry {
+        TableEntry::Object(ObjectInfo { x, y, id })
+    }
+
+    fn neutopia() -> Neutopia {
+        let data = RomBuilder::new()
+            .room(
+                5,
+                0x03,
+                RoomSpec {
+                    warps: vec![0x01, 0x02],
+                    enemies: vec![0x10],
+                    objects: vec![
+                        object(2, 5, 0x4c),
+                        TableEntry::Unknown0b([0x01, 0x02, 0x03]),
+                        object(2, 5, 0x20),
+                    ],
+                },
+            )
+            .build()
+            .unwrap();
+        Neutopia::new(&data).unwrap()
+    }
+
+    #[test]
+    fn test_export_import() {
+        let mut n = neutopia();
+        let mut data = n.export();
+        assert_eq!(data.areas.len(), AREA_COUNT);
+        assert_eq!(data.conditionals.len(), 1);
+
+        // Rooms can't grow so drop a warp to make space for the enemy.
+        data.areas[5].rooms[3].warps = vec![0x01];
+        data.areas[5].rooms[3].enemies = vec![0x11, 0x12];
+        data.areas[5].room_order.swap(0, 1);
+        data.areas[6].chest_table[0].item_id = 0x10;
+        n.import(data.clone()).unwrap();
+
+        let written = Neutopia::new(&n.write().unwrap()).unwrap();
+        let after = written.export();
+        assert_eq!(after.areas[5].rooms[3].warps, vec![0x01]);
+        assert_eq!(after.areas[5].rooms[3].enemies, vec![0x11, 0x12]);
+        assert_eq!(after.areas[5].room_order[..2], [0x01, 0x00]);
+        assert_eq!(after.areas[6].chest_table[0].item_id, 0x10);
+        assert_eq!(after.conditionals, data.conditionals);
+        assert_eq!(roundtrip::compare(&n, &written), vec![]);
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let mut n = neutopia();
+        let err = |n: &mut Neutopia, data: GameData| n.import(data).unwrap_err().to_string();
+
+        let mut data = n.export();
+        data.areas[5].rooms.pop();
+        assert_eq!(err(&mut n, data), "area 05 has 63 rooms, need 64");
+
+        // Object 0x54 is past the chest objects so it isn't a chest.
+        let chests = n.filter_chests(|_| true).unwrap().len();
+        let mut data = n.export();
+        data.areas[5].rooms[0].objects.push(object(1, 1, 0x54));
+        n.import(data).unwrap();
+        assert_eq!(n.filter_chests(|_| true).unwrap().len(), chests);
+
+        let mut data = n.export();
+        data.areas[1].rooms[0].enemies.push(0x01);
+        assert_eq!(
+            err(&mut n, data),
+            "area 01 rooms and chests can't be changed"
+        );
+
+        let mut data = n.export();
+        let c = data.conditionals[0].clone();
+        data.conditionals.push(c);
+        assert!(err(&mut n, data).starts_with("duplicate conditional"));
+
+        // Room orders of areas 0-3 are written in place so can change.
+        let mut data = n.export();
+        data.areas[1].room_order.reverse();
+        n.import(data).unwrap();
+    }
+}
@@ -0,0 +1,200 @@
+//! Checks that parsing corrupted ROMs returns errors instead of panicking.
+
+use std::panic;
+
+use super::{
+    bank,
+    rom::{object::ObjectInfo, object::TableEntry},
+    rommap,
+    synthetic::{RomBuilder, RoomSpec},
+    util, Neutopia, NeutopiaRom,
+};
+
+// xorshift64* so runs are reproducible without pulling in `rand`.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+// Terminators, bank boundaries, and the last chest object id.
+const INTERESTING: &[u8] = &[0x00, 0x1f, 0x20, 0x40, 0x4f, 0x54, 0x7f, 0x80, 0xff];
+
+fn write_pointer(data: &mut [u8], offset: usize, target: usize) {
+    let pointer = util::rom_offset_to_pointer(target as u32).unwrap();
+    data[offset..offset + 3].copy_from_slice(&pointer);
+}
+
+// Every room has a warp, an enemy, and a chest.
+fn minimal_rom() -> Vec<u8> {
+    let room = RoomSpec {
+        warps: vec![0x12, 0x34],
+        enemies: vec![0x01],
+        objects: vec![TableEntry::Object(ObjectInfo {
+            x: 2,
+            y: 1,
+            id: 0x4c,
+        })],
+    };
+    let mut builder = RomBuilder::new();
+    for area in &mut builder.areas {
+        area.rooms = vec![room.clone(); area.rooms.len()];
+    }
+    builder.build().unwrap()
+}
+
+// Returns the (offset, length) of every range of `data` that is parsed.
+fn parsed_regions(data: &[u8]) -> Vec<(usize, usize)> {
+    NeutopiaRom::new(data)
+        .unwrap()
+        .data_intervals()
+        .get_intervals()
+        .iter()
+        .map(|i| (i.start, i.end - i.start))
+        .collect()
+}
+
+// Returns the end of the last parsed range of `data`.
+fn data_end(data: &[u8]) -> usize {
+    parsed_regions(data)
+        .iter()
+        .map(|(start, len)| start + len)
+        .max()
+        .unwrap()
+}
+
+// Runs the whole parse and write path, which may fail but must not panic.
+fn parse(data: &[u8]) {
+    if let Ok(rom) = NeutopiaRom::new(data) {
+        rom.data_intervals();
+    }
+    if let Ok(n) = Neutopia::new(data) {
+        let _ = n.write();
+    }
+}
+
+fn check(name: &str, seed: u64, data: &[u8]) {
+    let result = panic::catch_unwind(|| parse(data));
+    assert!(result.is_ok(), "{} input with seed {} panicked", name, seed);
+}
+
+#[test]
+fn test_minimal_rom_parses() {
+    let data = minimal_rom();
+    let n = Neutopia::new(&data).unwrap();
+    assert_eq!(n.areas.len(), 0x10);
+    assert_eq!(n.areas[0].rooms[0].warps, vec![0x12, 0x34]);
+    assert_eq!(n.filter_chests(|_| true).unwrap().len(), 0x10 * 0x40);
+    n.write().unwrap();
+}
+
+#[test]
+fn test_mutated_roms() {
+    let base = minimal_rom();
+    let regions = parsed_regions(&base);
+    for seed in 1..=500 {
+        let mut rng = Rng(seed);
+        let mut data = base.clone();
+        for _ in 0..1 + rng.below(8) {
+            let (start, len) = regions[rng.below(regions.len())];
+            let offset = start + rng.below(len);
+            data[offset] = if rng.below(2) == 0 {
+                INTERESTING[rng.below(INTERESTING.len())]
+            } else {
+                rng.next() as u8
+            };
+        }
+        check("mutated", seed, &data);
+    }
+}
+
+#[test]
+fn test_truncated_roms() {
+    let base = minimal_rom();
+    let end = data_end(&base);
+    for seed in 1..=100 {
+        let mut rng = Rng(seed);
+        let len = rommap::AREA_TABLE + rng.below(end - rommap::AREA_TABLE);
+        check("truncated", seed, &base[..len]);
+    }
+    check("empty", 0, &[]);
+}
+
+#[test]
+fn test_random_roms() {
+    let end = data_end(&minimal_rom());
+    for seed in 1..=100 {
+        let mut rng = Rng(seed);
+        let mut data: Vec<u8> = (0..bank::ROM_LEN).map(|_| rng.next() as u8).collect();
+        // Point the tables somewhere random but in range so parsing gets
+        // past the first pointer.
+        for i in 0..rommap::AREA_TABLE_COUNT {
+            let target = rommap::AREA_TABLE + rng.below(end - rommap::AREA_TABLE);
+            write_pointer(&mut data, rommap::AREA_TABLE + i * 3, target);
+        }
+        check("random", seed, &data);
+    }
+}
+
+#[test]
+fn test_errors_have_locations() {
+    let err = |data: &[u8]| match Neutopia::new(data) {
+        Ok(_) => panic!("parse succeeded"),
+        Err(e) => e.to_string(),
+    };
+    let base = minimal_rom();
+    let rom = NeutopiaRom::new(&base).unwrap();
+    let room = &rom.room_info_tables[0][&0];
+    let room_addr = room.base_addr as usize;
+    let objects = room.object_table_pointer as usize;
+
+    // Warp table pointer after the enemy table pointer.
+    let mut data = base.clone();
+    write_pointer(&mut data, room_addr, objects);
+    assert_eq!(
+        err(&data),
+        format!(
+            "can't parse room 00:00 at {:05x}: warp table at {:05x}: \
+             range {:05x}-{:05x} is inverted",
+            room_addr, objects, objects, room.enemy_table_pointer
+        )
+    );
+
+    // Unknown object table entry in place of the terminator.
+    let mut data = base.clone();
+    data[objects + 3] = 0x99;
+    assert_eq!(
+        err(&data),
+        format!(
+            "can't parse room 00:00 at {:05x}: object table at {:05x}: \
+             unparsed input at +3: {:02x?}",
+            room_addr,
+            objects,
+            &data[objects + 3..objects + 11]
+        )
+    );
+
+    // Object 0x54 is past the chest objects so it isn't a chest.
+    let mut data = base.clone();
+    data[objects + 2] = 0x54;
+    let n = Neutopia::new(&data).unwrap();
+    assert!(n
+        .filter_chests(|c| c.area == 0 && c.room == 0)
+        .unwrap()
+        .is_empty());
+
+    assert_eq!(
+        err(&base[..rommap::CHEST_TABLE]),
+        "can't decode chest table pointers at 5041e: \
+         data only 0 bytes in length.  Need at least 48"
+    );
+}
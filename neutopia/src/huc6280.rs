@@ -0,0 +1,375 @@
+//! HuC6280 instruction set.
+//!
+//! The table covers the 65C02 instructions along with the HuC6280
+//! additions (block transfers, `tst`, `st0`-`st2`, `tam`/`tma`, etc.).
+
+use Mode::*;
+
+/// Operand addressing mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// No operand.  Includes the accumulator forms (i.e. `inc`).
+    Implied,
+    /// `#$nn`
+    Immediate,
+    /// `$nn`
+    ZeroPage,
+    /// `$nn,x`
+    ZeroPageX,
+    /// `$nn,y`
+    ZeroPageY,
+    /// `($nn)`
+    ZeroPageIndirect,
+    /// `($nn,x)`
+    ZeroPageIndirectX,
+    /// `($nn),y`
+    ZeroPageIndirectY,
+    /// `$nnnn`
+    Absolute,
+    /// `$nnnn,x`
+    AbsoluteX,
+    /// `$nnnn,y`
+    AbsoluteY,
+    /// `($nnnn)`
+    AbsoluteIndirect,
+    /// `($nnnn,x)`
+    AbsoluteIndirectX,
+    /// Branch target encoded as a signed offset from the next instruction.
+    Relative,
+    /// `$nn, target` as used by `bbr`/`bbs`.
+    ZeroPageRelative,
+    /// `#$nn, $nn`
+    ImmediateZeroPage,
+    /// `#$nn, $nn,x`
+    ImmediateZeroPageX,
+    /// `#$nn, $nnnn`
+    ImmediateAbsolute,
+    /// `#$nn, $nnnn,x`
+    ImmediateAbsoluteX,
+    /// `$ssss, $dddd, $llll` block transfers.
+    Block,
+}
+
+impl Mode {
+    /// Number of operand bytes following the opcode.
+    pub fn operand_len(self) -> usize {
+        match self {
+            Implied => 0,
+            Immediate | ZeroPage | ZeroPageX | ZeroPageY | ZeroPageIndirect | ZeroPageIndirectX
+            | ZeroPageIndirectY | Relative => 1,
+            Absolute | AbsoluteX | AbsoluteY | AbsoluteIndirect | AbsoluteIndirectX
+            | ZeroPageRelative | ImmediateZeroPage | ImmediateZeroPageX => 2,
+            ImmediateAbsolute | ImmediateAbsoluteX => 3,
+            Block => 6,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Opcode {
+    pub code: u8,
+    pub mnemonic: &'static str,
+    pub mode: Mode,
+}
+
+impl Opcode {
+    /// Length of the instruction including the opcode byte.
+    pub fn instruction_len(&self) -> usize {
+        1 + self.mode.operand_len()
+    }
+}
+
+const fn op(code: u8, mnemonic: &'static str, mode: Mode) -> Opcode {
+    Opcode {
+        code,
+        mnemonic,
+        mode,
+    }
+}
+
+/// All documented opcodes sorted by opcode byte.
+pub static OPCODES: [Opcode; 233] = [
+    op(0x00, "brk", Implied),
+    op(0x01, "ora", ZeroPageIndirectX),
+    op(0x02, "sxy", Implied),
+    op(0x03, "st0", Immediate),
+    op(0x04, "tsb", ZeroPage),
+    op(0x05, "ora", ZeroPage),
+    op(0x06, "asl", ZeroPage),
+    op(0x07, "rmb0", ZeroPage),
+    op(0x08, "php", Implied),
+    op(0x09, "ora", Immediate),
+    op(0x0a, "asl", Implied),
+    op(0x0c, "tsb", Absolute),
+    op(0x0d, "ora", Absolute),
+    op(0x0e, "asl", Absolute),
+    op(0x0f, "bbr0", ZeroPageRelative),
+    op(0x10, "bpl", Relative),
+    op(0x11, "ora", ZeroPageIndirectY),
+    op(0x12, "ora", ZeroPageIndirect),
+    op(0x13, "st1", Immediate),
+    op(0x14, "trb", ZeroPage),
+    op(0x15, "ora", ZeroPageX),
+    op(0x16, "asl", ZeroPageX),
+    op(0x17, "rmb1", ZeroPage),
+    op(0x18, "clc", Implied),
+    op(0x19, "ora", AbsoluteY),
+    op(0x1a, "inc", Implied),
+    op(0x1c, "trb", Absolute),
+    op(0x1d, "ora", AbsoluteX),
+    op(0x1e, "asl", AbsoluteX),
+    op(0x1f, "bbr1", ZeroPageRelative),
+    op(0x20, "jsr", Absolute),
+    op(0x21, "and", ZeroPageIndirectX),
+    op(0x22, "sax", Implied),
+    op(0x23, "st2", Immediate),
+    op(0x24, "bit", ZeroPage),
+    op(0x25, "and", ZeroPage),
+    op(0x26, "rol", ZeroPage),
+    op(0x27, "rmb2", ZeroPage),
+    op(0x28, "plp", Implied),
+    op(0x29, "and", Immediate),
+    op(0x2a, "rol", Implied),
+    op(0x2c, "bit", Absolute),
+    op(0x2d, "and", Absolute),
+    op(0x2e, "rol", Absolute),
+    op(0x2f, "bbr2", ZeroPageRelative),
+    op(0x30, "bmi", Relative),
+    op(0x31, "and", ZeroPageIndirectY),
+    op(0x32, "and", ZeroPageIndirect),
+    op(0x34, "bit", ZeroPageX),
+    op(0x35, "and", ZeroPageX),
+    op(0x36, "rol", ZeroPageX),
+    op(0x37, "rmb3", ZeroPage),
+    op(0x38, "sec", Implied),
+    op(0x39, "and", AbsoluteY),
+    op(0x3c, "bit", AbsoluteX),
+    op(0x3d, "and", AbsoluteX),
+    op(0x3e, "rol", AbsoluteX),
+    op(0x3f, "bbr3", ZeroPageRelative),
+    op(0x40, "rti", Implied),
+    op(0x41, "eor", ZeroPageIndirectX),
+    op(0x42, "say", Implied),
+    op(0x43, "tma", Immediate),
+    op(0x44, "bsr", Relative),
+    op(0x45, "eor", ZeroPage),
+    op(0x46, "lsr", ZeroPage),
+    op(0x47, "rmb4", ZeroPage),
+    op(0x48, "pha", Implied),
+    op(0x49, "eor", Immediate),
+    op(0x4a, "lsr", Implied),
+    op(0x4c, "jmp", Absolute),
+    op(0x4d, "eor", Absolute),
+    op(0x4e, "lsr", Absolute),
+    op(0x4f, "bbr4", ZeroPageRelative),
+    op(0x50, "bvc", Relative),
+    op(0x51, "eor", ZeroPageIndirectY),
+    op(0x52, "eor", ZeroPageIndirect),
+    op(0x53, "tam", Immediate),
+    op(0x54, "csl", Implied),
+    op(0x55, "eor", ZeroPageX),
+    op(0x56, "lsr", ZeroPageX),
+    op(0x57, "rmb5", ZeroPage),
+    op(0x58, "cli", Implied),
+    op(0x59, "eor", AbsoluteY),
+    op(0x5a, "phy", Implied),
+    op(0x5d, "eor", AbsoluteX),
+    op(0x5e, "lsr", AbsoluteX),
+    op(0x5f, "bbr5", ZeroPageRelative),
+    op(0x60, "rts", Implied),
+    op(0x61, "adc", ZeroPageIndirectX),
+    op(0x62, "cla", Implied),
+    op(0x64, "stz", ZeroPage),
+    op(0x65, "adc", ZeroPage),
+    op(0x66, "ror", ZeroPage),
+    op(0x67, "rmb6", ZeroPage),
+    op(0x68, "pla", Implied),
+    op(0x69, "adc", Immediate),
+    op(0x6a, "ror", Implied),
+    op(0x6c, "jmp", AbsoluteIndirect),
+    op(0x6d, "adc", Absolute),
+    op(0x6e, "ror", Absolute),
+    op(0x6f, "bbr6", ZeroPageRelative),
+    op(0x70, "bvs", Relative),
+    op(0x71, "adc", ZeroPageIndirectY),
+    op(0x72, "adc", ZeroPageIndirect),
+    op(0x73, "tii", Block),
+    op(0x74, "stz", ZeroPageX),
+    op(0x75, "adc", ZeroPageX),
+    op(0x76, "ror", ZeroPageX),
+    op(0x77, "rmb7", ZeroPage),
+    op(0x78, "sei", Implied),
+    op(0x79, "adc", AbsoluteY),
+    op(0x7a, "ply", Implied),
+    op(0x7c, "jmp", AbsoluteIndirectX),
+    op(0x7d, "adc", AbsoluteX),
+    op(0x7e, "ror", AbsoluteX),
+    op(0x7f, "bbr7", ZeroPageRelative),
+    op(0x80, "bra", Relative),
+    op(0x81, "sta", ZeroPageIndirectX),
+    op(0x82, "clx", Implied),
+    op(0x83, "tst", ImmediateZeroPage),
+    op(0x84, "sty", ZeroPage),
+    op(0x85, "sta", ZeroPage),
+    op(0x86, "stx", ZeroPage),
+    op(0x87, "smb0", ZeroPage),
+    op(0x88, "dey", Implied),
+    op(0x89, "bit", Immediate),
+    op(0x8a, "txa", Implied),
+    op(0x8c, "sty", Absolute),
+    op(0x8d, "sta", Absolute),
+    op(0x8e, "stx", Absolute),
+    op(0x8f, "bbs0", ZeroPageRelative),
+    op(0x90, "bcc", Relative),
+    op(0x91, "sta", ZeroPageIndirectY),
+    op(0x92, "sta", ZeroPageIndirect),
+    op(0x93, "tst", ImmediateAbsolute),
+    op(0x94, "sty", ZeroPageX),
+    op(0x95, "sta", ZeroPageX),
+    op(0x96, "stx", ZeroPageY),
+    op(0x97, "smb1", ZeroPage),
+    op(0x98, "tya", Implied),
+    op(0x99, "sta", AbsoluteY),
+    op(0x9a, "txs", Implied),
+    op(0x9c, "stz", Absolute),
+    op(0x9d, "sta", AbsoluteX),
+    op(0x9e, "stz", AbsoluteX),
+    op(0x9f, "bbs1", ZeroPageRelative),
+    op(0xa0, "ldy", Immediate),
+    op(0xa1, "lda", ZeroPageIndirectX),
+    op(0xa2, "ldx", Immediate),
+    op(0xa3, "tst", ImmediateZeroPageX),
+    op(0xa4, "ldy", ZeroPage),
+    op(0xa5, "lda", ZeroPage),
+    op(0xa6, "ldx", ZeroPage),
+    op(0xa7, "smb2", ZeroPage),
+    op(0xa8, "tay", Implied),
+    op(0xa9, "lda", Immediate),
+    op(0xaa, "tax", Implied),
+    op(0xac, "ldy", Absolute),
+    op(0xad, "lda", Absolute),
+    op(0xae, "ldx", Absolute),
+    op(0xaf, "bbs2", ZeroPageRelative),
+    op(0xb0, "bcs", Relative),
+    op(0xb1, "lda", ZeroPageIndirectY),
+    op(0xb2, "lda", ZeroPageIndirect),
+    op(0xb3, "tst", ImmediateAbsoluteX),
+    op(0xb4, "ldy", ZeroPageX),
+    op(0xb5, "lda", ZeroPageX),
+    op(0xb6, "ldx", ZeroPageY),
+    op(0xb7, "smb3", ZeroPage),
+    op(0xb8, "clv", Implied),
+    op(0xb9, "lda", AbsoluteY),
+    op(0xba, "tsx", Implied),
+    op(0xbc, "ldy", AbsoluteX),
+    op(0xbd, "lda", AbsoluteX),
+    op(0xbe, "ldx", AbsoluteY),
+    op(0xbf, "bbs3", ZeroPageRelative),
+    op(0xc0, "cpy", Immediate),
+    op(0xc1, "cmp", ZeroPageIndirectX),
+    op(0xc2, "cly", Implied),
+    op(0xc3, "tdd", Block),
+    op(0xc4, "cpy", ZeroPage),
+    op(0xc5, "cmp", ZeroPage),
+    op(0xc6, "dec", ZeroPage),
+    op(0xc7, "smb4", ZeroPage),
+    op(0xc8, "iny", Implied),
+    op(0xc9, "cmp", Immediate),
+    op(0xca, "dex", Implied),
+    op(0xcc, "cpy", Absolute),
+    op(0xcd, "cmp", Absolute),
+    op(0xce, "dec", Absolute),
+    op(0xcf, "bbs4", ZeroPageRelative),
+    op(0xd0, "bne", Relative),
+    op(0xd1, "cmp", ZeroPageIndirectY),
+    op(0xd2, "cmp", ZeroPageIndirect),
+    op(0xd3, "tin", Block),
+    op(0xd4, "csh", Implied),
+    op(0xd5, "cmp", ZeroPageX),
+    op(0xd6, "dec", ZeroPageX),
+    op(0xd7, "smb5", ZeroPage),
+    op(0xd8, "cld", Implied),
+    op(0xd9, "cmp", AbsoluteY),
+    op(0xda, "phx", Implied),
+    op(0xdd, "cmp", AbsoluteX),
+    op(0xde, "dec", AbsoluteX),
+    op(0xdf, "bbs5", ZeroPageRelative),
+    op(0xe0, "cpx", Immediate),
+    op(0xe1, "sbc", ZeroPageIndirectX),
+    op(0xe3, "tia", Block),
+    op(0xe4, "cpx", ZeroPage),
+    op(0xe5, "sbc", ZeroPage),
+    op(0xe6, "inc", ZeroPage),
+    op(0xe7, "smb6", ZeroPage),
+    op(0xe8, "inx", Implied),
+    op(0xe9, "sbc", Immediate),
+    op(0xea, "nop", Implied),
+    op(0xec, "cpx", Absolute),
+    op(0xed, "sbc", Absolute),
+    op(0xee, "inc", Absolute),
+    op(0xef, "bbs6", ZeroPageRelative),
+    op(0xf0, "beq", Relative),
+    op(0xf1, "sbc", ZeroPageIndirectY),
+    op(0xf2, "sbc", ZeroPageIndirect),
+    op(0xf3, "tai", Block),
+    op(0xf4, "set", Implied),
+    op(0xf5, "sbc", ZeroPageX),
+    op(0xf6, "inc", ZeroPageX),
+    op(0xf7, "smb7", ZeroPage),
+    op(0xf8, "sed", Implied),
+    op(0xf9, "sbc", AbsoluteY),
+    op(0xfa, "plx", Implied),
+    op(0xfd, "sbc", AbsoluteX),
+    op(0xfe, "inc", AbsoluteX),
+    op(0xff, "bbs7", ZeroPageRelative),
+];
+
+/// Look up the instruction for an opcode byte.
+pub fn decode(code: u8) -> Option<&'static Opcode> {
+    OPCODES
+        .binary_search_by_key(&code, |op| op.code)
+        .ok()
+        .map(|i| &OPCODES[i])
+}
+
+/// Look up the opcode for `mnemonic` in addressing `mode`.
+pub fn encode(mnemonic: &str, mode: Mode) -> Option<&'static Opcode> {
+    OPCODES
+        .iter()
+        .find(|op| op.mode == mode && op.mnemonic.eq_ignore_ascii_case(mnemonic))
+}
+
+/// Returns true if `mnemonic` is an instruction in any addressing mode.
+pub fn is_mnemonic(mnemonic: &str) -> bool {
+    OPCODES
+        .iter()
+        .any(|op| op.mnemonic.eq_ignore_ascii_case(mnemonic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_sorted() {
+        for pair in OPCODES.windows(2) {
+            assert!(pair[0].code < pair[1].code, "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        assert_eq!(encode("lda", Immediate).unwrap().code, 0xa9);
+        assert_eq!(encode("LDA", AbsoluteX).unwrap().code, 0xbd);
+        assert_eq!(encode("cla", Implied).unwrap().code, 0x62);
+        assert_eq!(encode("tii", Block).unwrap().instruction_len(), 7);
+        assert!(encode("sta", Immediate).is_none());
+
+        let op = decode(0x20).unwrap();
+        assert_eq!((op.mnemonic, op.mode), ("jsr", Absolute));
+        let op = decode(0x80).unwrap();
+        assert_eq!((op.mnemonic, op.mode), ("bra", Relative));
+        assert!(is_mnemonic("bbr3"));
+        assert!(!is_mnemonic("origin"));
+    }
+}
@@ -27,6 +27,23 @@ impl<T: Ord + Copy + Debug> Interval<T> {
             || (other.start <= self.start && self.start <= other.end)
     }
 
+    /// Returns true if `self` and `other` share at least one value.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    /// Returns the interval covered by both `self` and `other`, if any.
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        if self.overlaps(other) {
+            Some(Self {
+                start: max(self.start, other.start),
+                end: min(self.end, other.end),
+            })
+        } else {
+            None
+        }
+    }
+
     /// Merge `other` into this interval
     ///
     /// Panics if the intervals can't merge.
@@ -87,6 +104,28 @@ impl<T: Ord + Copy + Debug> IntervalStore<T> {
         intervals.sort();
         intervals
     }
+
+    /// Returns true if every value in [`start`, `end`) is in the store.
+    pub fn covers(&self, start: T, end: T) -> bool {
+        self.intervals
+            .iter()
+            .any(|i| i.start <= start && end <= i.end)
+    }
+
+    /// Return a sorted Vec of the intervals covered by both `self` and
+    /// `other`.
+    pub fn intersection(&self, other: &Self) -> Vec<Interval<T>> {
+        let mut intersections = Vec::new();
+        for a in &self.intervals {
+            for b in &other.intervals {
+                if let Some(i) = a.intersection(b) {
+                    intersections.push(i);
+                }
+            }
+        }
+        intersections.sort();
+        intersections
+    }
 }
 
 #[cfg(test)]
@@ -122,6 +161,37 @@ mod tests {
         assert_eq!(intervals, vec![Interval { start: 0, end: 6 }]);
     }
 
+    #[test]
+    pub fn store_covers() {
+        let mut store = IntervalStore::new();
+        store.add(0u32, 2);
+        store.add(2, 4);
+        store.add(6, 8);
+        assert!(store.covers(1, 4));
+        assert!(store.covers(6, 8));
+        assert!(!store.covers(3, 7));
+        assert!(!store.covers(8, 9));
+    }
+
+    #[test]
+    pub fn store_intersection() {
+        let mut a = IntervalStore::new();
+        a.add(0u32, 4);
+        a.add(8, 12);
+        let mut b = IntervalStore::new();
+        b.add(4, 6);
+        b.add(2, 3);
+        b.add(10, 20);
+        assert_eq!(
+            a.intersection(&b),
+            vec![
+                Interval { start: 2, end: 3 },
+                Interval { start: 10, end: 12 }
+            ]
+        );
+        assert_eq!(b.intersection(&a), a.intersection(&b));
+    }
+
     #[test]
     pub fn full_overlap() {
         let mut store = IntervalStore::new();
@@ -0,0 +1,387 @@
+//! Reader and writer for the IPS patch format.
+
+use std::io::prelude::*;
+
+use byteorder::{BigEndian, WriteBytesExt};
+use failure::{format_err, Error};
+
+/// A run of bytes written to `offset`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hunk {
+    pub offset: u32,
+    pub data: Vec<u8>,
+}
+
+// IPS records are limited to a 16 bit length.
+const MAX_RECORD_LEN: usize = 0xffff;
+
+// IPS offsets are 24 bits.
+const MAX_OFFSET: u32 = 0xff_ffff;
+
+// A record at this offset would be read back as the "EOF" footer.
+const EOF_OFFSET: u32 = 0x45_4f46;
+
+// An RLE record is 8 bytes.  Splitting a run out of the middle of a data
+// record costs an extra 5 byte record header so only runs longer than that
+// are worth encoding.
+const MIN_RLE_RUN: usize = 8 + 5 + 1;
+
+#[derive(Debug, PartialEq)]
+enum Record<'a> {
+    Data { offset: u32, data: &'a [u8] },
+    Rle { offset: u32, len: u16, value: u8 },
+}
+
+impl<'a> Record<'a> {
+    fn offset(&self) -> u32 {
+        match self {
+            Self::Data { offset, .. } | Self::Rle { offset, .. } => *offset,
+        }
+    }
+}
+
+// Add data records for `hunk.data[start..end]`.
+fn push_data_records<'a>(records: &mut Vec<Record<'a>>, hunk: &'a Hunk, start: usize, end: usize) {
+    let mut start = start;
+    while start < end {
+        let len = (end - start).min(MAX_RECORD_LEN);
+        records.push(Record::Data {
+            offset: hunk.offset + start as u32,
+            data: &hunk.data[start..start + len],
+        });
+        start += len;
+    }
+}
+
+// Split a hunk into IPS records, using RLE for long runs of a single value
+// and keeping each record within the maximum record length.
+fn records(hunk: &Hunk) -> Vec<Record<'_>> {
+    let mut records = Vec::new();
+    let data = &hunk.data;
+    let mut data_start = 0;
+    let mut i = 0;
+
+    while i < data.len() {
+        let value = data[i];
+        let run = data[i..].iter().take_while(|b| **b == value).count();
+        if run >= MIN_RLE_RUN {
+            push_data_records(&mut records, hunk, data_start, i);
+            let mut remaining = run;
+            while remaining > 0 {
+                let len = remaining.min(MAX_RECORD_LEN);
+                records.push(Record::Rle {
+                    offset: hunk.offset + (i + run - remaining) as u32,
+                    len: len as u16,
+                    value,
+                });
+                remaining -= len;
+            }
+            data_start = i + run;
+        }
+        i += run;
+    }
+    push_data_records(&mut records, hunk, data_start, data.len());
+
+    records
+}
+
+/// Write `hunks` as an IPS patch, optionally truncating the patched file to
+/// `truncate` bytes.
+pub fn write_ips(w: &mut impl Write, hunks: &[Hunk], truncate: Option<u32>) -> Result<(), Error> {
+    w.write_all(b"PATCH")?;
+    for hunk in hunks {
+        for record in records(hunk) {
+            let offset = record.offset();
+            if offset > MAX_OFFSET {
+                return Err(format_err!(
+                    "offset {:06x} does not fit in an IPS patch",
+                    offset
+                ));
+            }
+            if offset == EOF_OFFSET {
+                return Err(format_err!(
+                    "offset {:06x} can't be encoded because it collides with the IPS EOF marker",
+                    offset
+                ));
+            }
+
+            w.write_u24::<BigEndian>(offset)?;
+            match record {
+                Record::Data { data, .. } => {
+                    w.write_u16::<BigEndian>(data.len() as u16)?;
+                    w.write_all(data)?;
+                }
+                Record::Rle { len, value, .. } => {
+                    w.write_u16::<BigEndian>(0)?;
+                    w.write_u16::<BigEndian>(len)?;
+                    w.write_u8(value)?;
+                }
+            }
+        }
+    }
+    w.write_all(b"EOF")?;
+
+    if let Some(len) = truncate {
+        if len > MAX_OFFSET {
+            return Err(format_err!(
+                "truncation length {:06x} does not fit in an IPS patch",
+                len
+            ));
+        }
+        w.write_u24::<BigEndian>(len)?;
+    }
+    Ok(())
+}
+
+/// Returns a hunk for each run of bytes in `target` that differ from
+/// `source`, including any bytes past the end of `source`.
+pub fn diff(source: &[u8], target: &[u8]) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut last_diff = None;
+
+    for (offset, b) in target.iter().enumerate() {
+        if source.get(offset) == Some(b) {
+            continue;
+        }
+        match (last_diff, hunks.last_mut()) {
+            (Some(last), Some(hunk)) if last + 1 == offset => hunk.data.push(*b),
+            _ => hunks.push(Hunk {
+                offset: offset as u32,
+                data: vec![*b],
+            }),
+        }
+        last_diff = Some(offset);
+    }
+
+    hunks
+}
+
+/// Create a patch that turns `source` into `target`.
+pub fn create_patch(source: &[u8], target: &[u8]) -> Result<Vec<u8>, Error> {
+    let truncate = if target.len() < source.len() {
+        Some(target.len() as u32)
+    } else {
+        None
+    };
+
+    let mut patch = Vec::new();
+    write_ips(&mut patch, &diff(source, target), truncate)?;
+    Ok(patch)
+}
+
+fn read_u24(patch: &[u8], pos: usize) -> Result<u32, Error> {
+    let b = patch
+        .get(pos..pos + 3)
+        .ok_or_else(|| format_err!("unexpected end of patch at {:x}", pos))?;
+    Ok((b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32)
+}
+
+fn read_u16(patch: &[u8], pos: usize) -> Result<usize, Error> {
+    let b = patch
+        .get(pos..pos + 2)
+        .ok_or_else(|| format_err!("unexpected end of patch at {:x}", pos))?;
+    Ok((b[0] as usize) << 8 | b[1] as usize)
+}
+
+/// Read the records of an IPS `patch` as hunks, with run-length encoded
+/// records expanded, along with the truncation length if it has one.
+pub fn read_ips(patch: &[u8]) -> Result<(Vec<Hunk>, Option<u32>), Error> {
+    if !patch.starts_with(b"PATCH") {
+        return Err(format_err!("not an IPS patch"));
+    }
+
+    let mut hunks = Vec::new();
+    let mut pos = 5;
+    // The "EOF" footer is always the end of the records since no record
+    // may start at that offset.  Parsing it as a record instead makes some
+    // truncation lengths ambiguous.
+    while patch.get(pos..pos + 3) != Some(b"EOF") {
+        let offset = read_u24(patch, pos)?;
+        let len = read_u16(patch, pos + 3)?;
+        pos += 5;
+
+        let data = if len == 0 {
+            let len = read_u16(patch, pos)?;
+            let value = *patch
+                .get(pos + 2)
+                .ok_or_else(|| format_err!("unexpected end of patch at {:x}", pos + 2))?;
+            pos += 3;
+            vec![value; len]
+        } else {
+            let payload = patch
+                .get(pos..pos + len)
+                .ok_or_else(|| format_err!("unexpected end of patch at {:x}", pos))?;
+            pos += len;
+            payload.to_vec()
+        };
+        hunks.push(Hunk { offset, data });
+    }
+    pos += 3;
+
+    let truncate = match patch.len() - pos {
+        0 => None,
+        3 => Some(read_u24(patch, pos)?),
+        n => return Err(format_err!("{} unexpected bytes after IPS footer", n)),
+    };
+
+    Ok((hunks, truncate))
+}
+
+/// Apply `patch` to `source` returning the patched data.
+///
+/// The data is extended as needed by records written past its end.
+pub fn apply_patch(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    let (hunks, truncate) = read_ips(patch)?;
+
+    let mut data = source.to_vec();
+    for hunk in &hunks {
+        let offset = hunk.offset as usize;
+        let end = offset + hunk.data.len();
+        if end > data.len() {
+            data.resize(end, 0);
+        }
+        data[offset..end].copy_from_slice(&hunk.data);
+    }
+    if let Some(len) = truncate {
+        data.truncate(len as usize);
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(offset: u32, data: &[u8]) -> Hunk {
+        Hunk {
+            offset,
+            data: data.to_vec(),
+        }
+    }
+
+    fn ips(hunks: &[Hunk], truncate: Option<u32>) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_ips(&mut data, hunks, truncate).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_write_ips() {
+        assert_eq!(
+            ips(&[hunk(0x012345, &[0xaa, 0xbb])], None),
+            b"PATCH\x01\x23\x45\x00\x02\xaa\xbbEOF".to_vec()
+        );
+        assert_eq!(
+            ips(&[hunk(0x10, &[0xaa])], Some(0x060000)),
+            b"PATCH\x00\x00\x10\x00\x01\xaaEOF\x06\x00\x00".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_write_ips_rle() {
+        let mut data = vec![0x01];
+        data.extend_from_slice(&[0xee; 20]);
+        data.push(0x02);
+        assert_eq!(
+            ips(&[hunk(0x100, &data)], None),
+            b"PATCH\
+              \x00\x01\x00\x00\x01\x01\
+              \x00\x01\x01\x00\x00\x00\x14\xee\
+              \x00\x01\x15\x00\x01\x02\
+              EOF"
+            .to_vec()
+        );
+
+        // Short runs aren't worth encoding.
+        let data = [0xee; MIN_RLE_RUN - 1];
+        let short = hunk(0, &data);
+        let records = records(&short);
+        assert_eq!(
+            records,
+            vec![Record::Data {
+                offset: 0,
+                data: &data
+            }]
+        );
+    }
+
+    #[test]
+    fn test_write_ips_large_hunk() {
+        let data: Vec<u8> = (0..0x18000).map(|i| i as u8).collect();
+        let patch = ips(&[hunk(0x20000, &data)], None);
+
+        let (hunks, truncate) = read_ips(&patch).unwrap();
+        assert_eq!(
+            hunks,
+            vec![
+                hunk(0x20000, &data[..0xffff]),
+                hunk(0x20000 + 0xffff, &data[0xffff..])
+            ]
+        );
+        assert_eq!(truncate, None);
+
+        let data = vec![0x55; 0x18000];
+        let patch = ips(&[hunk(0x20000, &data)], None);
+        let (hunks, _) = read_ips(&patch).unwrap();
+        let payloads: Vec<u8> = hunks.into_iter().flat_map(|h| h.data).collect();
+        assert_eq!(payloads, data);
+    }
+
+    #[test]
+    fn test_read_ips() {
+        let patch = b"PATCH\
+              \x00\x01\x00\x00\x02\xaa\xbb\
+              \x00\x02\x00\x00\x00\x00\x04\xee\
+              EOF\x06\x00\x00";
+        assert_eq!(
+            read_ips(patch).unwrap(),
+            (
+                vec![hunk(0x100, &[0xaa, 0xbb]), hunk(0x200, &[0xee; 4])],
+                Some(0x060000)
+            )
+        );
+
+        assert!(read_ips(b"NOT A PATCH").is_err());
+        assert!(read_ips(b"PATCH\x00\x01\x00\x00\x02\xaaEOF").is_err());
+        assert!(read_ips(b"PATCHEOF\x00").is_err());
+    }
+
+    #[test]
+    fn test_write_ips_rejects_eof_offset() {
+        let mut data = Vec::new();
+        assert!(write_ips(&mut data, &[hunk(EOF_OFFSET, &[0])], None).is_err());
+        assert!(write_ips(&mut data, &[hunk(MAX_OFFSET + 1, &[0])], None).is_err());
+        assert!(write_ips(&mut data, &[], Some(MAX_OFFSET + 1)).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let source = [0x00, 0x01, 0x02, 0x03];
+        let target = [0x00, 0xff, 0xfe, 0x03, 0x04];
+        assert_eq!(
+            diff(&source, &target),
+            vec![hunk(1, &[0xff, 0xfe]), hunk(4, &[0x04])]
+        );
+    }
+
+    #[test]
+    fn test_patch_round_trip() {
+        let source: Vec<u8> = (0..0x400).map(|i| i as u8).collect();
+        let mut target = source.clone();
+        target[0x10..0x30].copy_from_slice(&[0x55; 0x20]);
+        target[0x200] = 0;
+        let patch = create_patch(&source, &target).unwrap();
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+
+        let longer = [&source[..], b"more"].concat();
+        let patch = create_patch(&source, &longer).unwrap();
+        assert_eq!(apply_patch(&source, &patch).unwrap(), longer);
+
+        let shorter = &source[..0x100];
+        let patch = create_patch(&source, shorter).unwrap();
+        assert_eq!(apply_patch(&source, &patch).unwrap(), shorter);
+
+        assert!(apply_patch(&source, b"NOT A PATCH").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! Accounting of which ROM ranges have been modified and by whom.
+//!
+//! Patches and the data writer each claim the ranges they write.  A claim
+//! that overlaps a range owned by someone else is an error so that one
+//! can't silently clobber the other.
+
+use std::fmt;
+
+use failure::{format_err, Error};
+
+use super::interval::Interval;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LedgerEntry {
+    /// Description of who modified the range (i.e. "patch intro-skip").
+    pub owner: String,
+    pub range: Interval<usize>,
+}
+
+impl fmt::Display for LedgerEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:05x}-{:05x})",
+            self.owner, self.range.start, self.range.end
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Ledger {
+    entries: Vec<LedgerEntry>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Record that `owner` modified [`start`, `end`) without checking for
+    /// overlaps.
+    pub fn record(&mut self, owner: &str, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        self.entries.push(LedgerEntry {
+            owner: owner.to_string(),
+            range: Interval { start, end },
+        });
+    }
+
+    /// Record that `owner` modified [`start`, `end`).
+    ///
+    /// Returns an error if any part of the range was already modified by a
+    /// different owner.
+    pub fn claim(&mut self, owner: &str, start: usize, end: usize) -> Result<(), Error> {
+        let range = Interval { start, end };
+        let conflicts: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|e| e.owner != owner && e.range.overlaps(&range))
+            .map(|e| e.to_string())
+            .collect();
+
+        if !conflicts.is_empty() {
+            return Err(format_err!(
+                "{} ({:05x}-{:05x}) overlaps {}",
+                owner,
+                start,
+                end,
+                conflicts.join(", ")
+            ));
+        }
+
+        self.record(owner, start, end);
+        Ok(())
+    }
+
+    /// Returns the entries in the order they were recorded.
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claim() {
+        let mut ledger = Ledger::new();
+        ledger.claim("a", 0x10, 0x20).unwrap();
+        // The same owner may write over its own ranges.
+        ledger.claim("a", 0x18, 0x28).unwrap();
+        // Adjacent ranges don't overlap.
+        ledger.claim("b", 0x28, 0x30).unwrap();
+
+        let err = ledger.claim("c", 0x1f, 0x29).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "c (0001f-00029) overlaps a (00010-00020), a (00018-00028), b (00028-00030)"
+        );
+        assert_eq!(ledger.entries().len(), 3);
+    }
+
+    #[test]
+    fn test_record() {
+        let mut ledger = Ledger::new();
+        ledger.record("a", 0x10, 0x20);
+        ledger.record("b", 0x10, 0x20);
+        ledger.record("c", 0x30, 0x30);
+        assert_eq!(ledger.entries().len(), 2);
+        assert!(ledger.claim("d", 0x00, 0x11).is_err());
+    }
+}
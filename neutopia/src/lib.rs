@@ -1,28 +1,75 @@
 use std::collections::HashMap;
 use std::io::{prelude::*, Cursor, SeekFrom};
+use std::ops::RangeInclusive;
 
 use failure::{format_err, Error};
-
+use serde::{Deserialize, Serialize};
+
+pub mod bank;
+pub mod bps;
+pub mod diff;
+pub mod disasm;
+pub mod export;
+pub mod huc6280;
 pub mod interval;
+pub mod ips;
+pub mod ledger;
+pub mod render;
 pub mod rom;
 pub mod rommap;
+pub mod roundtrip;
+pub mod synthetic;
+pub mod text;
 pub mod util;
+pub mod validate;
 pub mod verify;
 
+#[cfg(test)]
+mod fuzz;
+
+pub use ledger::Ledger;
 pub use rom::NeutopiaRom;
-pub use verify::{verify, RomInfo};
+pub use verify::{verify, verify_known, verify_supported, RomInfo};
+
+/// Areas whose rooms and chests [`Neutopia::write`] rewrites.  The rest are
+/// left as they are in the ROM.
+pub const REWRITTEN_AREAS: RangeInclusive<usize> = 0x4..=0xf;
+
+/// Object id of the chest using the first entry of its area's chest table.
+pub const FIRST_CHEST_OBJECT: u8 = 0x4c;
+
+// Returns entry `id` of `chest_table`, which room `room_idx` of
+// `area_idx` references.
+fn table_chest(
+    chest_table: &[rom::Chest],
+    area_idx: usize,
+    room_idx: usize,
+    id: u8,
+) -> Result<&rom::Chest, Error> {
+    chest_table.get(id as usize).ok_or_else(|| {
+        format_err!(
+            "room {:02x}:{:02x} references chest {} of {}",
+            area_idx,
+            room_idx,
+            id,
+            chest_table.len()
+        )
+    })
+}
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Room {
     pub warps: Vec<u8>,
     pub enemies: Vec<u8>,
     pub objects: Vec<rom::object::TableEntry>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Area {
     pub rooms: Vec<Room>,
     pub chest_table: Vec<rom::Chest>,
+    /// Order the area's rooms are laid out in on the map.
+    pub room_order: Vec<u8>,
 }
 
 #[derive(Clone, Debug)]
@@ -33,7 +80,7 @@ pub struct Chest {
     pub index: u8,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Conditional {
     pub data: Vec<rom::object::TableEntry>,
 }
@@ -69,15 +116,23 @@ impl Neutopia {
 
         for room_idx in 0u8..0x40 {
             let room = &room_info_table[&room_idx];
-            let mut object_table = rom::object::parse_object_table(&room.object_table)?;
-
+            let mut object_table =
+                rom::object::parse_object_table(&room.object_table).map_err(|e| {
+                    format_err!(
+                        "can't parse object table for room {:02x}:{:02x} at {:05x}: {}",
+                        area_idx,
+                        room_idx,
+                        room.object_table_pointer,
+                        e
+                    )
+                })?;
             // First scan for conditionals, record them, then remove them from the
             // table entries.
             if object_table.len() > 2 {
                 let mut i = 0;
                 while (i + 2) < object_table.len() {
                     if let Some(id) = object_table[i].chest_id() {
-                        let chest = &chest_table[id as usize];
+                        let chest = table_chest(chest_table, area_idx, room_idx as usize, id)?;
                         let next = object_table[i + 1].clone();
                         let next_next = object_table[i + 2].clone();
 
@@ -103,14 +158,16 @@ impl Neutopia {
             });
         }
 
+        let room_order = &self.n.room_order_tables[&self.n.room_order_pointers[area_idx]];
         self.areas.push(Area {
             rooms,
             chest_table: chest_table.clone(),
+            room_order: room_order.clone(),
         });
         Ok(())
     }
 
-    pub fn filter_chests(&self, filter: impl Fn(&Chest) -> bool) -> Vec<Chest> {
+    pub fn filter_chests(&self, filter: impl Fn(&Chest) -> bool) -> Result<Vec<Chest>, Error> {
         let mut chests = Vec::new();
 
         for (area_idx, area) in self.areas.iter().enumerate() {
@@ -119,7 +176,7 @@ impl Neutopia {
                 for entry in &room.objects {
                     if let Some(id) = entry.chest_id() {
                         let chest = Chest {
-                            info: area.chest_table[id as usize].clone(),
+                            info: table_chest(&area.chest_table, area_idx, room_idx, id)?.clone(),
                             area: area_idx as u8,
                             room: room_idx as u8,
                             index: chest_index,
@@ -133,7 +190,7 @@ impl Neutopia {
             }
         }
 
-        chests
+        Ok(chests)
     }
 
     fn get_table_id_for_chest(&self, chest: &Chest) -> Result<usize, Error> {
@@ -158,7 +215,7 @@ impl Neutopia {
             let id = self.get_table_id_for_chest(chest)?;
             let entry = self.areas[chest.area as usize]
                 .chest_table
-                .get_mut(id as usize)
+                .get_mut(id)
                 .ok_or_else(|| format_err!("incoherent chest id {:02x}", id))?;
 
             *entry = chest.info.clone();
@@ -167,30 +224,171 @@ impl Neutopia {
         Ok(())
     }
 
-    fn write_area(&self, area_idx: usize, rom_writer: &mut Cursor<Vec<u8>>) -> Result<u32, Error> {
+    // Returns the chest table entries of `area_idx` no chest object uses.
+    fn free_chest_ids(&self, area_idx: usize) -> Vec<u8> {
+        let area = &self.areas[area_idx];
+        // Only the first `CHEST_TABLE_LEN` entries have chest objects.
+        (0..area.chest_table.len().min(rom::CHEST_TABLE_LEN) as u8)
+            .filter(|id| {
+                !area
+                    .rooms
+                    .iter()
+                    .flat_map(|r| &r.objects)
+                    .any(|o| o.chest_id() == Some(*id))
+            })
+            .collect()
+    }
+
+    /// Add a chest holding `info` at (`x`, `y`) in a room.
+    ///
+    /// The chest takes the first unused entry of the area's chest table and
+    /// is added after the room's other chests so their indices don't
+    /// change.  Returns the new chest.
+    pub fn add_chest(
+        &mut self,
+        area_idx: u8,
+        room_idx: u8,
+        x: u8,
+        y: u8,
+        info: rom::Chest,
+    ) -> Result<Chest, Error> {
+        let location = format!("room {:02x}:{:02x}", area_idx, room_idx);
+        if !REWRITTEN_AREAS.contains(&(area_idx as usize)) {
+            return Err(format_err!(
+                "can't add a chest to {}, area {:02x} isn't rewritten",
+                location,
+                area_idx
+            ));
+        }
+        if x >= validate::ROOM_CELLS || y >= validate::ROOM_CELLS {
+            return Err(format_err!(
+                "can't add a chest to {} at ({}, {}), it's outside the room",
+                location,
+                x,
+                y
+            ));
+        }
+        let room = self.areas[area_idx as usize]
+            .rooms
+            .get(room_idx as usize)
+            .ok_or_else(|| format_err!("no {}", location))?;
+        if let Some(entry) = room
+            .objects
+            .iter()
+            .find(|o| o.object_info().map(|i| (i.x, i.y)) == Some((x, y)))
+        {
+            return Err(format_err!(
+                "can't add a chest to {} at ({}, {}), {} is there",
+                location,
+                x,
+                y,
+                entry
+            ));
+        }
+        let id = *self
+            .free_chest_ids(area_idx as usize)
+            .first()
+            .ok_or_else(|| {
+                format_err!(
+                    "can't add a chest to {}, all {} chests of area {:02x} are used",
+                    location,
+                    self.areas[area_idx as usize].chest_table.len(),
+                    area_idx
+                )
+            })?;
+
+        let area = &mut self.areas[area_idx as usize];
+        *area
+            .chest_table
+            .get_mut(id as usize)
+            .ok_or_else(|| format_err!("incoherent chest id {:02x}", id))? = info.clone();
+        let room = &mut area.rooms[room_idx as usize];
+        let index = room
+            .objects
+            .iter()
+            .filter(|o| o.chest_id().is_some())
+            .count() as u8;
+        room.objects
+            .push(rom::object::TableEntry::Object(rom::ObjectInfo {
+                x,
+                y,
+                id: FIRST_CHEST_OBJECT + id,
+            }));
+
+        Ok(Chest {
+            info,
+            area: area_idx,
+            room: room_idx,
+            index,
+        })
+    }
+
+    /// Remove `chest` from its room, returning what it held.
+    ///
+    /// Its chest table entry is left in place but becomes free for
+    /// [`Neutopia::add_chest`] once no other chest uses it.  The chest's
+    /// conditional is dropped if no other chest holds the same thing.
+    pub fn remove_chest(&mut self, chest: &Chest) -> Result<rom::Chest, Error> {
+        if !REWRITTEN_AREAS.contains(&(chest.area as usize)) {
+            return Err(format_err!(
+                "can't remove chests from area {:02x}, it isn't rewritten",
+                chest.area
+            ));
+        }
+        let id = self.get_table_id_for_chest(chest)?;
+
+        let area = &mut self.areas[chest.area as usize];
+        let info = area
+            .chest_table
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format_err!("incoherent chest id {:02x}", id))?;
+        let objects = &mut area.rooms[chest.room as usize].objects;
+        let pos = objects
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.chest_id().is_some())
+            .nth(chest.index as usize)
+            .map(|(pos, _)| pos)
+            .ok_or_else(|| format_err!("can't find chest {:?}", chest))?;
+        objects.remove(pos);
+
+        if self.filter_chests(|c| c.info == info)?.is_empty() {
+            self.conditionals.remove(&info);
+        }
+
+        Ok(info)
+    }
+
+    fn write_area(
+        &self,
+        area_idx: usize,
+        rom_writer: &mut Cursor<Vec<u8>>,
+        ledger: &mut Ledger,
+    ) -> Result<u32, Error> {
         let area = &self.areas[area_idx];
         let cur_offset = rom_writer.position();
 
         let mut room_ptrs = Cursor::new(Vec::new());
         let room_ptrs_offset = cur_offset;
         let room_data_offset = cur_offset + 0x40 * 3;
-        rom_writer.seek(SeekFrom::Start(room_data_offset as u64))?;
+        rom_writer.seek(SeekFrom::Start(room_data_offset))?;
         for room_idx in 0..0x40 {
             let room = &area.rooms[room_idx];
 
             let room_offset = rom_writer.position();
-            room_ptrs.write_all(&util::rom_offset_to_pointer(room_offset as u32))?;
+            room_ptrs.write_all(&util::rom_offset_to_pointer(room_offset as u32)?)?;
 
             // Add conditionals back to object_table.
             let mut object_table = room.objects.clone();
             for i in 0..object_table.len() {
                 if let Some(id) = object_table[i].chest_id() {
-                    let chest = &area.chest_table[id as usize];
+                    let chest = table_chest(&area.chest_table, area_idx, room_idx, id)?;
                     let loc = match object_table[i].loc() {
                         Some(loc) => loc,
                         _ => continue,
                     };
-                    if let Some(cond) = self.conditionals.get(&chest) {
+                    if let Some(cond) = self.conditionals.get(chest) {
                         for j in 0..cond.data.len() {
                             let mut entry = cond.data[j].clone();
                             if let rom::object::TableEntry::Object(ref mut o) = entry {
@@ -224,68 +422,158 @@ impl Neutopia {
             // Rewind and write table pointers.
             let room_end_pos = rom_writer.position();
             rom_writer.seek(SeekFrom::Start(room_offset))?;
-            rom_writer.write_all(&util::rom_offset_to_pointer(warp_table_ptr))?;
-            rom_writer.write_all(&util::rom_offset_to_pointer(enemy_table_ptr))?;
-            rom_writer.write_all(&util::rom_offset_to_pointer(object_table_ptr))?;
+            rom_writer.write_all(&util::rom_offset_to_pointer(warp_table_ptr)?)?;
+            rom_writer.write_all(&util::rom_offset_to_pointer(enemy_table_ptr)?)?;
+            rom_writer.write_all(&util::rom_offset_to_pointer(object_table_ptr)?)?;
             rom_writer.seek(SeekFrom::Start(room_end_pos))?;
         }
 
         // Record the end of the area.
         let next_offset = rom_writer.position() as u32;
+        ledger.claim(
+            &format!("area {:02x} room data", area_idx),
+            room_ptrs_offset as usize,
+            next_offset as usize,
+        )?;
 
         // Rewind and write out the pointer to the room data.
-        rom_writer.seek(SeekFrom::Start(room_ptrs_offset as u64))?;
+        rom_writer.seek(SeekFrom::Start(room_ptrs_offset))?;
         rom_writer.write_all(room_ptrs.get_ref())?;
 
         // And finally write out new area pointer.
-        rom_writer.seek(SeekFrom::Start(
-            rommap::AREA_TABLE as u64 + area_idx as u64 * 3,
-        ))?;
-        rom_writer.write_all(&util::rom_offset_to_pointer(room_ptrs_offset as u32))?;
+        let area_ptr_offset = rommap::AREA_TABLE + area_idx * 3;
+        ledger.claim("area table", area_ptr_offset, area_ptr_offset + 3)?;
+        rom_writer.seek(SeekFrom::Start(area_ptr_offset as u64))?;
+        rom_writer.write_all(&util::rom_offset_to_pointer(room_ptrs_offset as u32)?)?;
 
         Ok(next_offset)
     }
 
+    // Returns the end of the rewritten areas' room data in the ROM that was
+    // parsed.  Area 10 shares area c's rooms once written so its original
+    // rooms count too.
+    fn room_data_end(&self) -> usize {
+        REWRITTEN_AREAS
+            .chain(Some(0x10))
+            .filter_map(|area_idx| self.n.room_info_intervals.get(&(area_idx as u8)))
+            .flat_map(|intervals| intervals.get_intervals())
+            .map(|interval| interval.end)
+            .max()
+            .unwrap_or(0)
+    }
+
     pub fn write(&self) -> Result<Vec<u8>, Error> {
-        let mut rom_writer = Cursor::new(self.rom_data.clone());
+        self.write_tracked(&mut Ledger::new())
+    }
 
-        let area_range = 4..=0xf;
+    /// Write the ROM, claiming every range written in `ledger`.
+    ///
+    /// Fails if the data overlaps a range already claimed in `ledger` by
+    /// someone else (i.e. a patch) or with [`validate::ValidationErrors`] if
+    /// [`Neutopia::validate`] finds problems.
+    pub fn write_tracked(&self, ledger: &mut Ledger) -> Result<Vec<u8>, Error> {
+        let errors = self.validate();
+        if !errors.is_empty() {
+            return Err(validate::ValidationErrors(errors).into());
+        }
+
+        let mut rom_writer = Cursor::new(self.rom_data.clone());
 
         // First patch chest tables
-        for area_idx in area_range.clone() {
+        for area_idx in REWRITTEN_AREAS {
             let area = &self.areas[area_idx];
+            // Each area's relocated table has a fixed slot and area f's ends
+            // at the area table, so a longer table would overwrite either.
+            let slot_len = rommap::CHEST_TABLE_STRIDE / 4;
+            if area.chest_table.len() > slot_len {
+                return Err(format_err!(
+                    "area {:02x} has {} chests, its relocated chest table holds {}",
+                    area_idx,
+                    area.chest_table.len(),
+                    slot_len
+                ));
+            }
             // Relocate and write the new chest table.
-            let offset = 0x4fe00 + (0x20 * area_idx as u64);
+            let offset =
+                (rommap::RELOCATED_CHEST_TABLES + rommap::CHEST_TABLE_STRIDE * area_idx) as u64;
+            ledger.claim(
+                &format!("area {:02x} chest table", area_idx),
+                offset as usize,
+                offset as usize + area.chest_table.len() * 4,
+            )?;
             rom_writer.seek(SeekFrom::Start(offset))?;
             for chest in &area.chest_table {
                 chest.write(&mut rom_writer)?;
             }
 
             // Update the area's chest table pointer.
-            rom_writer.seek(SeekFrom::Start(
-                rommap::CHEST_TABLE as u64 + 3 * area_idx as u64,
-            ))?;
-            let ptr = util::rom_offset_to_pointer(offset as u32);
+            let ptr_offset = rommap::CHEST_TABLE + 3 * area_idx;
+            ledger.claim("chest table pointers", ptr_offset, ptr_offset + 3)?;
+            rom_writer.seek(SeekFrom::Start(ptr_offset as u64))?;
+            let ptr = util::rom_offset_to_pointer(offset as u32)?;
             rom_writer.write_all(&ptr)?;
         }
 
+        // Room order tables are written in place, and only if changed, so
+        // that areas sharing a table keep sharing it.
+        let mut room_orders: HashMap<u32, &Vec<u8>> = HashMap::new();
+        for (area_idx, area) in self.areas.iter().enumerate() {
+            let ptr = self.n.room_order_pointers[area_idx];
+            if let Some(other) = room_orders.insert(ptr, &area.room_order) {
+                if *other != area.room_order {
+                    return Err(format_err!(
+                        "area {:02x} shares its room order table at {:05x} but has a different order",
+                        area_idx,
+                        ptr
+                    ));
+                }
+            }
+        }
+        for (ptr, room_order) in room_orders {
+            if self.n.room_order_tables[&ptr] == *room_order {
+                continue;
+            }
+            if room_order.len() != 0x40 {
+                return Err(format_err!(
+                    "room order table at {:05x} has {} entries, need 64",
+                    ptr,
+                    room_order.len()
+                ));
+            }
+            ledger.claim("room order tables", ptr as usize, ptr as usize + 0x40)?;
+            rom_writer.seek(SeekFrom::Start(ptr as u64))?;
+            rom_writer.write_all(room_order)?;
+        }
+
         // Write out area data
 
-        // Beginning or area data starts where Area 4's data starts.
+        // Beginning or area data starts where Area 4's data starts.  It has
+        // to end before the data that followed the original rooms.
         let mut cur_offset = self.n.area_pointers[4];
+        let room_data_end = self.room_data_end();
         let mut offset_c = None;
-        for area_idx in area_range {
+        for area_idx in REWRITTEN_AREAS {
             if area_idx == 0xc {
                 offset_c = Some(cur_offset);
             }
             rom_writer.seek(SeekFrom::Start(cur_offset as u64))?;
-            cur_offset = self.write_area(area_idx, &mut rom_writer)?
+            cur_offset = self.write_area(area_idx, &mut rom_writer, ledger)?;
+            if cur_offset as usize > room_data_end {
+                return Err(format_err!(
+                    "area {:02x} room data ends at {:05x}, past the end of the original room data at {:05x}",
+                    area_idx,
+                    cur_offset,
+                    room_data_end
+                ));
+            }
         }
 
         // Lastly, fixup area 0x10's pointers to match 0xc's
         if let Some(offset) = offset_c {
-            rom_writer.seek(SeekFrom::Start(rommap::AREA_TABLE as u64 + 0x10 * 3))?;
-            rom_writer.write_all(&util::rom_offset_to_pointer(offset as u32))?;
+            let ptr_offset = rommap::AREA_TABLE + 0x10 * 3;
+            ledger.claim("area table", ptr_offset, ptr_offset + 3)?;
+            rom_writer.seek(SeekFrom::Start(ptr_offset as u64))?;
+            rom_writer.write_all(&util::rom_offset_to_pointer(offset)?)?;
         }
 
         Ok(rom_writer.into_inner())
@@ -315,4 +603,207 @@ pub fn area_name(area: u8) -> &'static str {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    // Tests that need the real game read it from the path in NEUTOPIA_ROM
+    // and are skipped if it isn't set.
+    fn user_rom() -> Option<Vec<u8>> {
+        let path = env::var_os("NEUTOPIA_ROM")?;
+        let data = fs::read(&path)
+            .unwrap_or_else(|e| panic!("can't read {}: {}", path.to_string_lossy(), e));
+        let info = verify(&data).unwrap();
+        if info.headered {
+            Some(data[bank::HEADER_LEN..].to_vec())
+        } else {
+            Some(data)
+        }
+    }
+
+    fn object(x: u8, y: u8, id: u8) -> rom::object::TableEntry {
+        rom::object::TableEntry::Object(rom::ObjectInfo { x, y, id })
+    }
+
+    fn chest(item_id: u8) -> rom::Chest {
+        rom::Chest {
+            item_id,
+            arg: 1,
+            text: 0x85,
+            unknown: 0x41,
+        }
+    }
+
+    #[test]
+    fn test_add_chest() {
+        // Room 04:12 has chests 0 and 1 and a door.  Room 04:00's warps
+        // make space for the added chest.
+        let data = synthetic::RomBuilder::new()
+            .room(
+                4,
+                0,
+                synthetic::RoomSpec {
+                    warps: vec![0x01, 0x02, 0x03],
+                    ..Default::default()
+                },
+            )
+            .room(
+                4,
+                0x12,
+                synthetic::RoomSpec {
+                    objects: vec![
+                        object(2, 2, 0x4d),
+                        rom::object::TableEntry::OpenDoor(0x01),
+                        object(3, 2, 0x4c),
+                    ],
+                    ..Default::default()
+                },
+            )
+            .build()
+            .unwrap();
+        let mut n = Neutopia::new(&data).unwrap();
+
+        let added = n.add_chest(4, 0x12, 5, 6, chest(0x16)).unwrap();
+        assert_eq!((added.area, added.room, added.index), (4, 0x12, 2));
+        assert_eq!(n.areas[4].chest_table[2], chest(0x16));
+        assert_eq!(n.areas[4].rooms[0x12].objects[3], object(5, 6, 0x4e));
+
+        // The chest survives writing the ROM out.
+        n.areas[4].rooms[0].warps.clear();
+        let written = Neutopia::new(&n.write().unwrap()).unwrap();
+        let chests = written
+            .filter_chests(|c| c.area == 4 && c.room == 0x12)
+            .unwrap();
+        assert_eq!(chests.len(), 3);
+        assert_eq!(chests[2].info, chest(0x16));
+
+        let err = |r: Result<Chest, Error>| r.unwrap_err().to_string();
+        assert_eq!(
+            err(n.add_chest(4, 0x12, 2, 2, chest(0x16))),
+            "can't add a chest to room 04:12 at (2, 2), object 0x4d @ (2,2) is there"
+        );
+        assert_eq!(
+            err(n.add_chest(4, 0x12, 16, 2, chest(0x16))),
+            "can't add a chest to room 04:12 at (16, 2), it's outside the room"
+        );
+        assert_eq!(
+            err(n.add_chest(0, 0, 1, 1, chest(0x16))),
+            "can't add a chest to room 00:00, area 00 isn't rewritten"
+        );
+        for i in 0..5 {
+            n.add_chest(4, i, 1, 1, chest(0x02)).unwrap();
+        }
+        assert_eq!(
+            err(n.add_chest(4, 5, 1, 1, chest(0x16))),
+            "can't add a chest to room 04:05, all 8 chests of area 04 are used"
+        );
+
+        // Only entries that are in the table can be used.
+        n.areas[5].chest_table.truncate(2);
+        n.add_chest(5, 0, 1, 1, chest(0x02)).unwrap();
+        n.add_chest(5, 1, 1, 1, chest(0x02)).unwrap();
+        assert_eq!(
+            err(n.add_chest(5, 2, 1, 1, chest(0x16))),
+            "can't add a chest to room 05:02, all 2 chests of area 05 are used"
+        );
+    }
+
+    #[test]
+    fn test_write_room_data_growth() {
+        let data = synthetic::RomBuilder::new()
+            .after_rooms(b"DATA")
+            .build()
+            .unwrap();
+        let mut n = Neutopia::new(&data).unwrap();
+        let end = n.room_data_end();
+        assert_eq!(&data[end..end + 4], b"DATA");
+
+        let written = n.write().unwrap();
+        assert_eq!(&written[end..end + 4], b"DATA");
+
+        n.areas[5].rooms[0].enemies.push(0x01);
+        assert_eq!(
+            n.write().unwrap_err().to_string(),
+            format!(
+                "area 0f room data ends at {:05x}, past the end of the original room data at {:05x}",
+                end + 1,
+                end
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_chest_table_slot() {
+        let mut n = Neutopia::new(&synthetic::RomBuilder::new().build().unwrap()).unwrap();
+        n.areas[0xf].chest_table.push(chest(0x16));
+        assert_eq!(
+            n.write().unwrap_err().to_string(),
+            "area 0f has 9 chests, its relocated chest table holds 8"
+        );
+    }
+
+    #[test]
+    fn test_remove_chest() {
+        let data = synthetic::RomBuilder::new()
+            .room(
+                4,
+                0x12,
+                synthetic::RoomSpec {
+                    objects: vec![
+                        object(2, 2, 0x4c),
+                        rom::object::TableEntry::Unknown0b([0x01, 0x02, 0x03]),
+                        object(2, 2, 0x20),
+                        object(3, 2, 0x4d),
+                    ],
+                    ..Default::default()
+                },
+            )
+            .chest(4, 0, chest(0x16))
+            .chest(4, 1, chest(0x17))
+            .build()
+            .unwrap();
+        let mut n = Neutopia::new(&data).unwrap();
+        assert!(n.conditionals.contains_key(&chest(0x16)));
+
+        let first = n.filter_chests(|c| c.area == 4 && c.room == 0x12).unwrap()[0].clone();
+        assert_eq!(n.remove_chest(&first).unwrap(), chest(0x16));
+        assert!(!n.conditionals.contains_key(&chest(0x16)));
+
+        let chests = n.filter_chests(|c| c.area == 4 && c.room == 0x12).unwrap();
+        assert_eq!(chests.len(), 1);
+        assert_eq!((chests[0].index, &chests[0].info), (0, &chest(0x17)));
+
+        // The freed entry is reused.
+        let added = n.add_chest(4, 0, 1, 1, chest(0x18)).unwrap();
+        assert_eq!(n.areas[4].chest_table[0], chest(0x18));
+        n.remove_chest(&added).unwrap();
+        let stale = Chest { index: 1, ..first };
+        assert!(n.remove_chest(&stale).is_err());
+        n.write().unwrap();
+
+        // A chest whose entry is past the end of the table is an error.
+        n.areas[4].chest_table.truncate(1);
+        assert_eq!(
+            n.filter_chests(|_| true).unwrap_err().to_string(),
+            "room 04:12 references chest 1 of 1"
+        );
+        assert_eq!(
+            n.remove_chest(&chests[0]).unwrap_err().to_string(),
+            "incoherent chest id 01"
+        );
+        assert!(n.write().is_err());
+    }
+
+    #[test]
+    fn test_round_trip_user_rom() {
+        let data = match user_rom() {
+            Some(data) => data,
+            None => return,
+        };
+
+        let diffs = roundtrip::round_trip(&data).unwrap();
+        let report: Vec<String> = diffs.iter().map(|d| d.to_string()).collect();
+        assert!(diffs.is_empty(), "{}", report.join("\n"));
+    }
+}
@@ -0,0 +1,217 @@
+//! Schematic images of rooms and area maps.
+//!
+//! Where the game keeps its room terrain, tile graphics, and palettes has
+//! not been located yet so these are schematics only: rooms are drawn as a
+//! grid of the positions objects can be placed at, with each object table
+//! entry filled in.  Nothing here shows how a room looks in the game.
+//!
+//! Decoding VRAM tiles, palettes, and metatile layouts into real room
+//! images is left for when that data is found, as is placing enemies,
+//! since the enemy table's format beyond one byte per enemy isn't known.
+
+use super::{rom::object::TableEntry, validate, Area, Room};
+
+const ROOM_CELLS: usize = validate::ROOM_CELLS as usize;
+pub const CELL_SIZE: usize = 16;
+pub const ROOM_SIZE: usize = ROOM_CELLS * CELL_SIZE;
+
+/// Areas are an 8x8 grid of rooms.
+pub const AREA_ROOMS: usize = 8;
+
+pub type Rgb = [u8; 3];
+
+const BACKGROUND: Rgb = [0x20, 0x20, 0x28];
+const GRID: Rgb = [0x38, 0x38, 0x44];
+const BORDER: Rgb = [0x80, 0x80, 0x90];
+const CHEST: Rgb = [0xf0, 0xc0, 0x20];
+const OBJECT: Rgb = [0xa0, 0xa0, 0xa0];
+const GATED: Rgb = [0x90, 0x50, 0xe0];
+const BURNABLE: Rgb = [0xf0, 0x70, 0x20];
+const HAZARD: Rgb = [0xe0, 0x30, 0x30];
+const ENEMY: Rgb = [0xff, 0x40, 0x80];
+
+/// An RGB image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// Rows of pixels from the top left.
+    pub pixels: Vec<Rgb>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, color: Rgb) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![color; width * height],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Fill a rectangle, clipped to the image.
+    pub fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        for y in y..(y + height).min(self.height) {
+            for x in x..(x + width).min(self.width) {
+                self.pixels[y * self.width + x] = color;
+            }
+        }
+    }
+
+    /// Copy `image` into this one with its top left at (`x`, `y`).
+    pub fn blit(&mut self, image: &Image, x: usize, y: usize) {
+        for row in 0..image.height.min(self.height.saturating_sub(y)) {
+            for col in 0..image.width.min(self.width.saturating_sub(x)) {
+                self.pixels[(y + row) * self.width + x + col] = image.pixel(col, row);
+            }
+        }
+    }
+
+    /// Returns the pixels as packed RGB bytes, e.g. for a PNG encoder.
+    pub fn to_rgb_bytes(&self) -> Vec<u8> {
+        self.pixels.iter().flatten().copied().collect()
+    }
+}
+
+fn entry_color(entry: &TableEntry) -> Option<Rgb> {
+    if entry.chest_id().is_some() {
+        return Some(CHEST);
+    }
+    Some(match entry {
+        TableEntry::Object(_) => OBJECT,
+        TableEntry::PushBlockGatedObject(_)
+        | TableEntry::EnemyGatedObject(_)
+        | TableEntry::BellGatedObject(_) => GATED,
+        TableEntry::Burnable(_) => BURNABLE,
+        TableEntry::OuchRope(_)
+        | TableEntry::ArrowLauncher(_)
+        | TableEntry::Swords(_)
+        | TableEntry::GhostSpawner(_)
+        | TableEntry::FireballSpawner(_) => HAZARD,
+        _ => return None,
+    })
+}
+
+/// Draw `room`'s object table entries on the room grid.
+///
+/// Entries without a position (doors, NPCs, and such) aren't drawn.  Enemy
+/// positions aren't known so each enemy table byte is drawn as a marker
+/// along the top edge.
+pub fn render_room(room: &Room) -> Image {
+    let mut image = Image::new(ROOM_SIZE, ROOM_SIZE, BACKGROUND);
+    for i in 0..ROOM_CELLS {
+        image.fill(i * CELL_SIZE, 0, 1, ROOM_SIZE, GRID);
+        image.fill(0, i * CELL_SIZE, ROOM_SIZE, 1, GRID);
+    }
+
+    for entry in &room.objects {
+        if let (Some(info), Some(color)) = (entry.object_info(), entry_color(entry)) {
+            let x = info.x as usize * CELL_SIZE;
+            let y = info.y as usize * CELL_SIZE;
+            image.fill(x + 2, y + 2, CELL_SIZE - 4, CELL_SIZE - 4, color);
+        }
+    }
+
+    for i in 0..room.enemies.len() {
+        image.fill(2 + i * 6, 2, 4, 4, ENEMY);
+    }
+
+    image
+}
+
+/// Draw every room of `area` laid out by its room order table.
+///
+/// Entry `i` of the table is the room shown at row `i / 8`, column `i % 8`,
+/// which is how `neutil doc` lays out the map.
+pub fn render_area(area: &Area) -> Image {
+    let size = AREA_ROOMS * ROOM_SIZE;
+    let mut image = Image::new(size, size, BACKGROUND);
+    for (i, room_idx) in area
+        .room_order
+        .iter()
+        .enumerate()
+        .take(AREA_ROOMS * AREA_ROOMS)
+    {
+        let x = (i % AREA_ROOMS) * ROOM_SIZE;
+        let y = (i / AREA_ROOMS) * ROOM_SIZE;
+        if let Some(room) = area.rooms.get(*room_idx as usize) {
+            image.blit(&render_room(room), x, y);
+        }
+        image.fill(x, y, ROOM_SIZE, 1, BORDER);
+        image.fill(x, y, 1, ROOM_SIZE, BORDER);
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom::object::ObjectInfo;
+
+    fn cell_color(image: &Image, x: usize, y: usize) -> Rgb {
+        image.pixel(x * CELL_SIZE + CELL_SIZE / 2, y * CELL_SIZE + CELL_SIZE / 2)
+    }
+
+    fn room(objects: Vec<TableEntry>) -> Room {
+        Room {
+            warps: Vec::new(),
+            enemies: vec![0x01, 0x02],
+            objects,
+        }
+    }
+
+    #[test]
+    fn test_render_room() {
+        let image = render_room(&room(vec![
+            TableEntry::Object(ObjectInfo {
+                x: 3,
+                y: 4,
+                id: 0x4c,
+            }),
+            TableEntry::Burnable(ObjectInfo {
+                x: 15,
+                y: 15,
+                id: 0x10,
+            }),
+            TableEntry::OpenDoor(0x01),
+        ]));
+        assert_eq!((image.width, image.height), (ROOM_SIZE, ROOM_SIZE));
+        assert_eq!(cell_color(&image, 3, 4), CHEST);
+        assert_eq!(cell_color(&image, 15, 15), BURNABLE);
+        assert_eq!(cell_color(&image, 4, 3), BACKGROUND);
+        assert_eq!(image.pixel(3, 3), ENEMY);
+        assert_eq!(image.pixel(9, 3), ENEMY);
+        assert_eq!(image.pixel(15, 3), BACKGROUND);
+        assert_eq!(image.to_rgb_bytes().len(), ROOM_SIZE * ROOM_SIZE * 3);
+    }
+
+    #[test]
+    fn test_render_area() {
+        let chest_room = room(vec![TableEntry::Object(ObjectInfo {
+            x: 1,
+            y: 1,
+            id: 0x4c,
+        })]);
+        let mut rooms = vec![room(Vec::new()); 0x40];
+        rooms[0x12] = chest_room;
+        let mut room_order: Vec<u8> = (0..0x40).collect();
+        room_order.swap(0x00, 0x12);
+        let area = Area {
+            rooms,
+            chest_table: Vec::new(),
+            room_order,
+        };
+
+        let image = render_area(&area);
+        assert_eq!(image.width, AREA_ROOMS * ROOM_SIZE);
+        // Room 12 is drawn in the top left.
+        assert_eq!(cell_color(&image, 1, 1), CHEST);
+        assert_eq!(
+            cell_color(&image, 2 * ROOM_CELLS + 1, 2 * ROOM_CELLS + 1),
+            BACKGROUND
+        );
+    }
+}
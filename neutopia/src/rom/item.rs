@@ -0,0 +1,197 @@
+use std::fmt;
+
+use failure::{format_err, Error};
+
+use super::Chest;
+
+/// Typed view of a chest's `item_id` and `arg` bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub enum Item {
+    Bombs(u8),
+    Medicine,
+    FireWand,
+    SkyBell,
+    Wings,
+    MoonbeamMoss,
+    MagicRing,
+    Sword(u8),
+    Armor(u8),
+    Shield(u8),
+    FalconShoes,
+    RainbowDrop,
+    BookOfRevival,
+    CrystalBall,
+    CryptKey,
+    /// Medallion for crypt `n` (1-8).
+    Medallion(u8),
+    /// An item id with no known meaning.  Holds the raw `item_id` and `arg`.
+    Unknown(u8, u8),
+}
+
+impl Item {
+    pub const BOMBS_ID: u8 = 0x00;
+    pub const MEDICINE_ID: u8 = 0x01;
+    pub const FIRE_WAND_ID: u8 = 0x02;
+    pub const SKY_BELL_ID: u8 = 0x03;
+    pub const WINGS_ID: u8 = 0x04;
+    pub const MOONBEAM_MOSS_ID: u8 = 0x05;
+    pub const MAGIC_RING_ID: u8 = 0x06;
+    pub const SWORD_ID: u8 = 0x08;
+    pub const ARMOR_ID: u8 = 0x09;
+    pub const SHIELD_ID: u8 = 0x0a;
+    pub const FALCON_SHOES_ID: u8 = 0x0b;
+    pub const RAINBOW_DROP_ID: u8 = 0x0c;
+    pub const BOOK_OF_REVIVAL_ID: u8 = 0x0d;
+    pub const CRYSTAL_BALL_ID: u8 = 0x10;
+    pub const CRYPT_KEY_ID: u8 = 0x11;
+    pub const MEDALLION_ID: u8 = 0x12;
+    pub const MEDALLION_COUNT: u8 = 8;
+
+    /// Decode an item from a chest's `item_id` and `arg` bytes.
+    pub fn from_ids(item_id: u8, arg: u8) -> Self {
+        match item_id {
+            Self::BOMBS_ID => Self::Bombs(arg),
+            Self::MEDICINE_ID => Self::Medicine,
+            Self::FIRE_WAND_ID => Self::FireWand,
+            Self::SKY_BELL_ID => Self::SkyBell,
+            Self::WINGS_ID => Self::Wings,
+            Self::MOONBEAM_MOSS_ID => Self::MoonbeamMoss,
+            Self::MAGIC_RING_ID => Self::MagicRing,
+            Self::SWORD_ID => Self::Sword(arg),
+            Self::ARMOR_ID => Self::Armor(arg),
+            Self::SHIELD_ID => Self::Shield(arg),
+            Self::FALCON_SHOES_ID => Self::FalconShoes,
+            Self::RAINBOW_DROP_ID => Self::RainbowDrop,
+            Self::BOOK_OF_REVIVAL_ID => Self::BookOfRevival,
+            Self::CRYSTAL_BALL_ID => Self::CrystalBall,
+            Self::CRYPT_KEY_ID => Self::CryptKey,
+            id if (Self::MEDALLION_ID..Self::MEDALLION_ID + Self::MEDALLION_COUNT)
+                .contains(&id) =>
+            {
+                Self::Medallion(id - Self::MEDALLION_ID + 1)
+            }
+            _ => Self::Unknown(item_id, arg),
+        }
+    }
+
+    /// Encode an item into a chest's `item_id` and `arg` bytes.
+    ///
+    /// Items that don't use `arg` encode it as 1 to match the vanilla chest
+    /// tables.  Fails for medallions of crypts other than 1-8.
+    pub fn to_ids(&self) -> Result<(u8, u8), Error> {
+        Ok(match *self {
+            Self::Bombs(n) => (Self::BOMBS_ID, n),
+            Self::Medicine => (Self::MEDICINE_ID, 1),
+            Self::FireWand => (Self::FIRE_WAND_ID, 1),
+            Self::SkyBell => (Self::SKY_BELL_ID, 1),
+            Self::Wings => (Self::WINGS_ID, 1),
+            Self::MoonbeamMoss => (Self::MOONBEAM_MOSS_ID, 1),
+            Self::MagicRing => (Self::MAGIC_RING_ID, 1),
+            Self::Sword(tier) => (Self::SWORD_ID, tier),
+            Self::Armor(tier) => (Self::ARMOR_ID, tier),
+            Self::Shield(tier) => (Self::SHIELD_ID, tier),
+            Self::FalconShoes => (Self::FALCON_SHOES_ID, 1),
+            Self::RainbowDrop => (Self::RAINBOW_DROP_ID, 1),
+            Self::BookOfRevival => (Self::BOOK_OF_REVIVAL_ID, 1),
+            Self::CrystalBall => (Self::CRYSTAL_BALL_ID, 1),
+            Self::CryptKey => (Self::CRYPT_KEY_ID, 1),
+            Self::Medallion(n) if (1..=Self::MEDALLION_COUNT).contains(&n) => {
+                (Self::MEDALLION_ID + n - 1, 1)
+            }
+            Self::Medallion(n) => return Err(format_err!("no medallion for crypt {}", n)),
+            Self::Unknown(item_id, arg) => (item_id, arg),
+        })
+    }
+
+    /// Returns true for items that are tied to the crypt they are found in.
+    pub fn is_area_locked(&self) -> bool {
+        matches!(self, Self::CrystalBall | Self::CryptKey)
+    }
+
+    pub fn is_medallion(&self) -> bool {
+        matches!(self, Self::Medallion(_))
+    }
+}
+
+impl From<&Chest> for Item {
+    fn from(chest: &Chest) -> Self {
+        Self::from_ids(chest.item_id, chest.arg)
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn tier_name(tier: u8) -> &'static str {
+            match tier {
+                1 => "Starter",
+                2 => "Bronze",
+                3 => "Steel",
+                4 => "Strongest",
+                _ => "Unknown",
+            }
+        }
+
+        match self {
+            Self::Bombs(n) => write!(f, "Bombs x{}", n),
+            Self::Medicine => write!(f, "Medicine"),
+            Self::FireWand => write!(f, "Fire Wand"),
+            Self::SkyBell => write!(f, "Sky Bell"),
+            Self::Wings => write!(f, "Wings"),
+            Self::MoonbeamMoss => write!(f, "Moonbeam Moss"),
+            Self::MagicRing => write!(f, "Magic Ring"),
+            Self::Sword(tier) => write!(f, "{} Sword", tier_name(*tier)),
+            Self::Armor(tier) => write!(f, "{} Armor", tier_name(*tier)),
+            Self::Shield(tier) => write!(f, "{} Shield", tier_name(*tier)),
+            Self::FalconShoes => write!(f, "Falcon Shoes"),
+            Self::RainbowDrop => write!(f, "Rainbow Drop"),
+            Self::BookOfRevival => write!(f, "Book of Revival"),
+            Self::CrystalBall => write!(f, "Crystal Ball"),
+            Self::CryptKey => write!(f, "Crypt Key"),
+            Self::Medallion(n) => write!(f, "Crypt {} Medallion", n),
+            Self::Unknown(0x07, _)
+            | Self::Unknown(0x0e, _)
+            | Self::Unknown(0x0f, _)
+            | Self::Unknown(0x20, _) => write!(f, "Placeholder"),
+            Self::Unknown(_, _) => write!(f, "Unknown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_round_trip() {
+        for item_id in 0..=0xff {
+            for arg in 0..=4 {
+                let item = Item::from_ids(item_id, arg);
+                let (new_id, new_arg) = item.to_ids().unwrap();
+                assert_eq!(new_id, item_id);
+                assert_eq!(Item::from_ids(new_id, new_arg), item);
+            }
+        }
+    }
+
+    #[test]
+    fn test_item_decode() {
+        assert_eq!(Item::from_ids(0x00, 4), Item::Bombs(4));
+        assert_eq!(Item::from_ids(0x08, 3), Item::Sword(3));
+        assert_eq!(Item::from_ids(0x12, 1), Item::Medallion(1));
+        assert_eq!(Item::from_ids(0x19, 1), Item::Medallion(8));
+        assert_eq!(Item::from_ids(0x1a, 1), Item::Unknown(0x1a, 1));
+        assert_eq!(Item::Sword(2).to_string(), "Bronze Sword");
+        assert_eq!(Item::Medallion(3).to_string(), "Crypt 3 Medallion");
+    }
+
+    #[test]
+    fn test_medallion_range() {
+        assert_eq!(Item::Medallion(8).to_ids().unwrap(), (0x19, 1));
+        for n in &[0, 9] {
+            assert_eq!(
+                Item::Medallion(*n).to_ids().unwrap_err().to_string(),
+                format!("no medallion for crypt {}", n)
+            );
+        }
+    }
+}
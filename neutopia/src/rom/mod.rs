@@ -2,11 +2,13 @@ use std::collections::HashMap;
 
 use failure::{format_err, Error};
 
-use super::{interval::IntervalStore, rommap, util};
+use super::{bank, interval::IntervalStore, rommap, util};
 
 mod chest;
+mod item;
 pub mod object;
-pub use chest::Chest;
+pub use chest::{Chest, CHEST_TABLE_LEN};
+pub use item::Item;
 pub use object::ObjectInfo;
 
 #[derive(Debug)]
@@ -34,16 +36,105 @@ pub struct NeutopiaRom {
     pub room_info_intervals: HashMap<u8, IntervalStore<usize>>,
 }
 
+// Converts a nom error into one that reports where in `data` parsing
+// stopped instead of echoing the remaining input.
+fn parse_error(data: &[u8], e: nom::Err<(&[u8], nom::error::ErrorKind)>) -> Error {
+    match e {
+        nom::Err::Error((i, kind)) | nom::Err::Failure((i, kind)) => {
+            format_err!("parse error at +{:x}: {:?}", data.len() - i.len(), kind)
+        }
+        nom::Err::Incomplete(_) => format_err!("parse error: incomplete input"),
+    }
+}
+
+// Describes the input left over after parsing `data`.
+fn unparsed_error(data: &[u8], rest: &[u8]) -> Error {
+    format_err!(
+        "unparsed input at +{:x}: {:02x?}",
+        data.len() - rest.len(),
+        &rest[..rest.len().min(8)]
+    )
+}
+
+fn read_pointer_table(
+    data: &[u8],
+    offset: usize,
+    entries: usize,
+    what: &str,
+) -> Result<Vec<u32>, Error> {
+    util::slice_from(data, offset)
+        .and_then(|table| util::decode_pointer_table(table, entries))
+        .map_err(|e| format_err!("can't decode {} at {:05x}: {}", what, offset, e))
+}
+
+impl Room {
+    fn parse(data: &[u8], offset: usize) -> Result<Room, Error> {
+        let ptrs = util::slice_from(data, offset)
+            .and_then(|table| util::decode_pointer_table(table, 3))
+            .map_err(|e| format_err!("can't decode table pointers: {}", e))?;
+        let warp_table_pointer = ptrs[0] as usize;
+        let enemy_table_pointer = ptrs[1] as usize;
+        let object_table_pointer = ptrs[2] as usize;
+
+        // The game reads the tables through a single page so none of them can
+        // be larger than a bank.
+        let table_data = |offset: usize| {
+            util::slice_from(data, offset).map(|table| &table[..table.len().min(bank::BANK_SIZE)])
+        };
+
+        // Warp tables have no terminator and run up to the enemy table.
+        let warp_table = util::slice(data, warp_table_pointer, enemy_table_pointer)
+            .and_then(|table| {
+                if table.len() > bank::BANK_SIZE {
+                    Err(format_err!("{:x} bytes is larger than a bank", table.len()))
+                } else {
+                    Ok(table.to_vec())
+                }
+            })
+            .map_err(|e| format_err!("warp table at {:05x}: {}", warp_table_pointer, e))?;
+        let enemy_table = table_data(enemy_table_pointer)
+            .and_then(util::read_object_table)
+            .map_err(|e| format_err!("enemy table at {:05x}: {}", enemy_table_pointer, e))?;
+        // Todo, clean this up once everything parses.
+        let object_table = table_data(object_table_pointer)
+            .and_then(|table| {
+                let len = object::object_table_len(table)?;
+                Ok(table[..len].to_vec())
+            })
+            .map_err(|e| format_err!("object table at {:05x}: {}", object_table_pointer, e))?;
+
+        Ok(Room {
+            base_addr: offset as u32,
+            warp_table_pointer: warp_table_pointer as u32,
+            enemy_table_pointer: enemy_table_pointer as u32,
+            object_table_pointer: object_table_pointer as u32,
+            warp_table,
+            enemy_table,
+            object_table,
+        })
+    }
+}
+
 impl NeutopiaRom {
     pub fn new(data: &[u8]) -> Result<NeutopiaRom, Error> {
-        let area_pointers =
-            util::decode_pointer_table(&data[rommap::AREA_TABLE..], rommap::AREA_TABLE_COUNT)?;
-        let room_order_pointers = util::decode_pointer_table(
-            &data[rommap::ROOM_ORDER_TABLE..],
+        let area_pointers = read_pointer_table(
+            data,
+            rommap::AREA_TABLE,
+            rommap::AREA_TABLE_COUNT,
+            "area table",
+        )?;
+        let room_order_pointers = read_pointer_table(
+            data,
+            rommap::ROOM_ORDER_TABLE,
             rommap::ROOM_ORDER_TABLE_COUNT,
+            "room order table",
+        )?;
+        let chest_table_pointers = read_pointer_table(
+            data,
+            rommap::CHEST_TABLE,
+            rommap::CHEST_TABLE_COUNT,
+            "chest table pointers",
         )?;
-        let chest_table_pointers =
-            util::decode_pointer_table(&data[rommap::CHEST_TABLE..], rommap::CHEST_TABLE_COUNT)?;
 
         let mut room_info_tables = Vec::new();
         let mut room_order_tables = HashMap::new();
@@ -55,55 +146,47 @@ impl NeutopiaRom {
             room_data_intervals.add(*area_ptr as usize, *area_ptr as usize + 0x40 * 3);
             let mut area_info = HashMap::new();
             for idx in 0..0x40 {
-                let offset = (*area_ptr as usize) + (idx as usize) * 3;
-                let offset = util::pointer_to_rom_offset(&data[offset..]).map_err(|e| {
-                    format_err!(
-                        "can't decode room pointer {:02x}:{:02x}: {}",
-                        area_idx,
-                        idx,
-                        e
-                    )
-                })? as usize;
+                let ptr_offset = (*area_ptr as usize) + (idx as usize) * 3;
+                let offset = util::slice_from(data, ptr_offset)
+                    .and_then(util::pointer_to_rom_offset)
+                    .map_err(|e| {
+                        format_err!(
+                            "can't decode room pointer {:02x}:{:02x} at {:05x}: {}",
+                            area_idx,
+                            idx,
+                            ptr_offset,
+                            e
+                        )
+                    })? as usize;
 
-                let ptrs = util::decode_pointer_table(&data[offset..], 3).map_err(|e| {
+                let room = Room::parse(data, offset).map_err(|e| {
                     format_err!(
-                        "can't decode room table pointers {:02x}:{:02x}: {}",
+                        "can't parse room {:02x}:{:02x} at {:05x}: {}",
                         area_idx,
                         idx,
+                        offset,
                         e
                     )
                 })?;
-                let warp_table_pointer = ptrs[0] as usize;
-                let enemy_table_pointer = ptrs[1] as usize;
-                let object_table_pointer = ptrs[2] as usize;
 
                 room_data_intervals.add(offset, offset + 3 * 3);
-
-                let warp_table = Vec::from(&data[warp_table_pointer..enemy_table_pointer]);
-                let enemy_table = util::read_object_table(&data[enemy_table_pointer..]);
-                // Todo, clean this up once everything parses.
-                let len = object::object_table_len(&data[object_table_pointer..])?;
-                let object_table = data[object_table_pointer..object_table_pointer + len].to_vec();
-
-                room_data_intervals.add(warp_table_pointer, warp_table_pointer + warp_table.len());
+                let warp_table_pointer = room.warp_table_pointer as usize;
+                let enemy_table_pointer = room.enemy_table_pointer as usize;
+                let object_table_pointer = room.object_table_pointer as usize;
+                room_data_intervals.add(
+                    warp_table_pointer,
+                    warp_table_pointer + room.warp_table.len(),
+                );
                 room_data_intervals.add(
                     enemy_table_pointer,
-                    enemy_table_pointer + enemy_table.len() + 1,
+                    enemy_table_pointer + room.enemy_table.len() + 1,
                 );
-                room_data_intervals.add(object_table_pointer, object_table_pointer + len + 1);
-
-                area_info.insert(
-                    idx as u8,
-                    Room {
-                        base_addr: offset as u32,
-                        warp_table_pointer: warp_table_pointer as u32,
-                        enemy_table_pointer: enemy_table_pointer as u32,
-                        object_table_pointer: object_table_pointer as u32,
-                        warp_table,
-                        enemy_table,
-                        object_table,
-                    },
+                room_data_intervals.add(
+                    object_table_pointer,
+                    object_table_pointer + room.object_table.len() + 1,
                 );
+
+                area_info.insert(idx as u8, room);
             }
             room_info_tables.push(area_info);
             room_info_intervals.insert(area_idx as u8, room_data_intervals);
@@ -111,13 +194,18 @@ impl NeutopiaRom {
 
         for room_order_ptr in &room_order_pointers {
             let offset = *room_order_ptr as usize;
-            let table = Vec::from(&data[offset..offset + 0x40]);
+            let table = util::slice(data, offset, offset + 0x40)
+                .map_err(|e| format_err!("can't read room order table: {}", e))?
+                .to_vec();
 
             room_order_tables.insert(*room_order_ptr, table);
         }
 
         for chest_table_ptr in &chest_table_pointers {
-            let table = chest::parse_chest_table(&data[*chest_table_ptr as usize..])?;
+            let offset = *chest_table_ptr as usize;
+            let table = util::slice_from(data, offset)
+                .and_then(chest::parse_chest_table)
+                .map_err(|e| format_err!("can't parse chest table at {:05x}: {}", offset, e))?;
             chest_tables.insert(*chest_table_ptr, table);
         }
 
@@ -131,4 +219,38 @@ impl NeutopiaRom {
             room_info_intervals,
         })
     }
+
+    /// Returns the ROM ranges that were parsed into this model.
+    ///
+    /// Any modification to these ranges made before parsing is carried by the
+    /// model and will be reproduced when it is written back out.
+    pub fn data_intervals(&self) -> IntervalStore<usize> {
+        let mut intervals = IntervalStore::new();
+        intervals.add(
+            rommap::AREA_TABLE,
+            rommap::AREA_TABLE + rommap::AREA_TABLE_COUNT * 3,
+        );
+        intervals.add(
+            rommap::ROOM_ORDER_TABLE,
+            rommap::ROOM_ORDER_TABLE + rommap::ROOM_ORDER_TABLE_COUNT * 3,
+        );
+        intervals.add(
+            rommap::CHEST_TABLE,
+            rommap::CHEST_TABLE + rommap::CHEST_TABLE_COUNT * 3,
+        );
+
+        for room_intervals in self.room_info_intervals.values() {
+            for interval in room_intervals.get_intervals() {
+                intervals.add(interval.start, interval.end);
+            }
+        }
+        for ptr in &self.room_order_pointers {
+            intervals.add(*ptr as usize, *ptr as usize + 0x40);
+        }
+        for (ptr, table) in &self.chest_tables {
+            intervals.add(*ptr as usize, *ptr as usize + table.len() * 4);
+        }
+
+        intervals
+    }
 }
@@ -2,15 +2,19 @@ use std::fmt;
 use std::io::prelude::*;
 
 use byteorder::WriteBytesExt;
-use failure::{format_err, Error};
+use failure::Error;
 use nom::{
     branch::alt,
     bytes::complete::{tag, take},
     multi::many0,
     IResult,
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq)]
+use super::CHEST_TABLE_LEN;
+use crate::FIRST_CHEST_OBJECT;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct ObjectInfo {
     pub x: u8,
     pub y: u8,
@@ -23,7 +27,7 @@ impl fmt::Display for ObjectInfo {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum TableEntry {
     Object(ObjectInfo),
     OpenDoor(u8),
@@ -80,19 +84,35 @@ impl TableEntry {
         Ok(())
     }
 
+    /// Returns the chest table entry this object opens, if it's a chest.
     pub fn chest_id(&self) -> Option<u8> {
         if let Self::Object(o) = self {
-            if 0x4c <= o.id && o.id <= (0x4c + 8) {
-                return Some(o.id - 0x4c);
+            let chests = FIRST_CHEST_OBJECT..FIRST_CHEST_OBJECT + CHEST_TABLE_LEN as u8;
+            if chests.contains(&o.id) {
+                return Some(o.id - FIRST_CHEST_OBJECT);
             }
         }
         None
     }
 
     pub fn is_conditional(&self) -> bool {
+        matches!(self, Self::Unknown0b(_))
+    }
+
+    /// Returns the id and position of entries placed in the room.
+    pub fn object_info(&self) -> Option<&ObjectInfo> {
         match self {
-            Self::Unknown0b(_) => true,
-            _ => false,
+            Self::Object(info)
+            | Self::PushBlockGatedObject(info)
+            | Self::EnemyGatedObject(info)
+            | Self::BellGatedObject(info)
+            | Self::Burnable(info)
+            | Self::OuchRope(info)
+            | Self::ArrowLauncher(info)
+            | Self::Swords(info)
+            | Self::GhostSpawner(info)
+            | Self::FireballSpawner(info) => Some(info),
+            _ => None,
         }
     }
 
@@ -377,11 +397,10 @@ fn parse_object_table_entry(i: &[u8]) -> IResult<&[u8], TableEntry> {
 }
 
 pub fn object_table_len(data: &[u8]) -> Result<usize, Error> {
-    let (i, _) =
-        many0(parse_object_table_entry)(data).map_err(|e| format_err!("parse error: {}", e))?;
+    let (i, _) = many0(parse_object_table_entry)(data).map_err(|e| super::parse_error(data, e))?;
 
     if !i.is_empty() && i[0] != 0xff {
-        return Err(format_err!("unparsed input: {:x?}", i));
+        return Err(super::unparsed_error(data, i));
     }
 
     Ok(data.len() - i.len())
@@ -389,10 +408,10 @@ pub fn object_table_len(data: &[u8]) -> Result<usize, Error> {
 
 pub fn parse_object_table(data: &[u8]) -> Result<Vec<TableEntry>, Error> {
     let (i, table) =
-        many0(parse_object_table_entry)(data).map_err(|e| format_err!("parse error: {}", e))?;
+        many0(parse_object_table_entry)(data).map_err(|e| super::parse_error(data, e))?;
 
     if !i.is_empty() {
-        return Err(format_err!("unparsed input: {:x?}", i));
+        return Err(super::unparsed_error(data, i));
     }
 
     Ok(table)
@@ -407,11 +426,29 @@ mod tests {
         let mut c = Cursor::new(Vec::new());
         entry.write(&mut c).unwrap();
         let enc_data = c.into_inner();
-        assert_eq!(&data[..], &enc_data[..]);
+        assert_eq!(data, &enc_data[..]);
 
         assert_eq!(parse_object_table_entry(data), Ok((&[][..], entry)));
     }
 
+    #[test]
+    fn test_chest_id() {
+        let object = |id| TableEntry::Object(ObjectInfo { x: 1, y: 1, id });
+        assert_eq!(object(0x4b).chest_id(), None);
+        assert_eq!(object(0x4c).chest_id(), Some(0));
+        assert_eq!(object(0x53).chest_id(), Some(7));
+        assert_eq!(object(0x54).chest_id(), None);
+        assert_eq!(
+            TableEntry::Burnable(ObjectInfo {
+                x: 1,
+                y: 1,
+                id: 0x4c
+            })
+            .chest_id(),
+            None
+        );
+    }
+
     #[test]
     fn test_parse_entries() {
         run_parse_test(
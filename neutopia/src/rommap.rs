@@ -1,3 +1,8 @@
+//! Locations of the game's data tables.
+//!
+//! These are offsets into the ROM without its header.  Use
+//! [`crate::bank`] to convert them to the bank and address the game uses.
+
 pub const AREA_TABLE: usize = 0x50000;
 pub const AREA_TABLE_COUNT: usize = 17;
 
@@ -6,3 +11,15 @@ pub const ROOM_ORDER_TABLE_COUNT: usize = 17;
 
 pub const CHEST_TABLE: usize = 0x5041e;
 pub const CHEST_TABLE_COUNT: usize = 16;
+
+// Relocated chest tables, one slot of `CHEST_TABLE_STRIDE` bytes per area
+// indexed from `RELOCATED_CHEST_TABLES`.  Only areas 4-f are relocated so
+// the first slot in use, area 4's, starts right after `RANDO_INFO` and the
+// last ends at `AREA_TABLE`.
+pub const RELOCATED_CHEST_TABLES: usize = 0x4fe00;
+pub const CHEST_TABLE_STRIDE: usize = 0x20;
+
+// Unused space ahead of the relocated chest tables where the randomizer
+// records how a ROM was generated.
+pub const RANDO_INFO: usize = 0x4fe00;
+pub const RANDO_INFO_LEN: usize = 0x80;
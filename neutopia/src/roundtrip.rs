@@ -0,0 +1,164 @@
+//! Verifies that writing a parsed ROM back out preserves its contents.
+//!
+//! [`Neutopia::write`] relocates chest tables and room data so the output
+//! isn't byte-identical to the input.  Instead the output is parsed again and
+//! every area, room, object, and chest is compared with the original.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use failure::Error;
+
+use super::{Neutopia, Room};
+
+/// A value that differs between the original and the rewritten ROM.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difference {
+    /// What differs, e.g. `area 04 room 12 object 3`.
+    pub location: String,
+    pub original: String,
+    pub written: String,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} became {}",
+            self.location, self.original, self.written
+        )
+    }
+}
+
+struct Differences(Vec<Difference>);
+
+impl Differences {
+    fn check<T: fmt::Debug + PartialEq>(&mut self, location: &str, original: &T, written: &T) {
+        if original != written {
+            self.0.push(Difference {
+                location: location.to_string(),
+                original: format!("{:x?}", original),
+                written: format!("{:x?}", written),
+            });
+        }
+    }
+
+    // Compares lists entry by entry so a single changed entry is reported
+    // on its own instead of as the whole list.
+    fn check_list<T: fmt::Debug + PartialEq>(
+        &mut self,
+        location: &str,
+        what: &str,
+        original: &[T],
+        written: &[T],
+    ) {
+        if original.len() != written.len() {
+            self.check(&format!("{} {}", location, what), &original, &written);
+            return;
+        }
+        for (i, (a, b)) in original.iter().zip(written).enumerate() {
+            self.check(&format!("{} {} {}", location, what, i), a, b);
+        }
+    }
+
+    fn check_room(&mut self, location: &str, original: &Room, written: &Room) {
+        self.check(
+            &format!("{} warps", location),
+            &original.warps,
+            &written.warps,
+        );
+        self.check(
+            &format!("{} enemies", location),
+            &original.enemies,
+            &written.enemies,
+        );
+        self.check_list(location, "object", &original.objects, &written.objects);
+    }
+}
+
+/// Returns every structural difference between `original` and `written`.
+pub fn compare(original: &Neutopia, written: &Neutopia) -> Vec<Difference> {
+    let mut diffs = Differences(Vec::new());
+
+    diffs.check("area count", &original.areas.len(), &written.areas.len());
+    for (area_idx, (a, b)) in original.areas.iter().zip(&written.areas).enumerate() {
+        let location = format!("area {:02x}", area_idx);
+        diffs.check_list(&location, "chest", &a.chest_table, &b.chest_table);
+        diffs.check(
+            &format!("{} room order", location),
+            &a.room_order,
+            &b.room_order,
+        );
+        diffs.check(
+            &format!("{} room count", location),
+            &a.rooms.len(),
+            &b.rooms.len(),
+        );
+        for (room_idx, (a, b)) in a.rooms.iter().zip(&b.rooms).enumerate() {
+            diffs.check_room(&format!("{} room {:02x}", location, room_idx), a, b);
+        }
+    }
+
+    let chests: BTreeSet<_> = original
+        .conditionals
+        .keys()
+        .chain(written.conditionals.keys())
+        .collect();
+    for chest in chests {
+        diffs.check(
+            &format!("conditional for {:x?}", chest),
+            &original.conditionals.get(chest).map(|c| &c.data),
+            &written.conditionals.get(chest).map(|c| &c.data),
+        );
+    }
+
+    diffs.0
+}
+
+/// Parse `data`, write it back out unchanged, and compare the result with
+/// the original.
+///
+/// Returns the differences found, which is empty if the round trip
+/// preserved everything.
+pub fn round_trip(data: &[u8]) -> Result<Vec<Difference>, Error> {
+    let original = Neutopia::new(data)?;
+    let written = Neutopia::new(&original.write()?)?;
+    Ok(compare(&original, &written))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom::object::TableEntry;
+    use crate::synthetic::RomBuilder;
+
+    #[test]
+    fn test_compare() {
+        let data = RomBuilder::new().build().unwrap();
+        let original = Neutopia::new(&data).unwrap();
+        let mut written = Neutopia::new(&data).unwrap();
+        assert_eq!(compare(&original, &written), vec![]);
+
+        written.areas[4].chest_table[1].item_id = 0x12;
+        written.areas[4].rooms[0x12]
+            .objects
+            .push(TableEntry::DarkRoom);
+        written.areas[5].rooms[0].enemies.push(0x02);
+        let diffs: Vec<String> = compare(&original, &written)
+            .iter()
+            .map(|d| d.location.clone())
+            .collect();
+        assert_eq!(
+            diffs,
+            vec![
+                "area 04 chest 1",
+                "area 04 room 12 object",
+                "area 05 room 00 enemies"
+            ]
+        );
+        assert_eq!(
+            compare(&original, &written)[2].to_string(),
+            "area 05 room 00 enemies: [] became [2]"
+        );
+    }
+}
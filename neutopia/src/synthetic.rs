@@ -0,0 +1,321 @@
+//! Builds synthetic ROM images so the data model can be tested without the
+//! game.
+//!
+//! The image only contains the tables in [`rommap`] and the room data they
+//! point to.  Everything else is zero.  Room data is laid out the way
+//! [`Neutopia::write`](crate::Neutopia::write) does with areas 4-f last.
+//! As in the game, rooms can't grow past their original end when the ROM
+//! is written back out.  [`RomBuilder::after_rooms`] puts data there to
+//! check that nothing overwrites it.
+
+use std::io::{prelude::*, Cursor, SeekFrom};
+
+use failure::{format_err, Error};
+
+use super::{
+    bank,
+    export::{AREA_COUNT, ROOM_COUNT},
+    rom::{object::TableEntry, Chest, CHEST_TABLE_LEN},
+    rommap, util,
+};
+
+const ROOM_ORDER_TABLES: usize = 0x50800;
+const CHEST_TABLES: usize = 0x50c40;
+const AREA_DATA: usize = 0x51000;
+
+// Area 0x10 shares the room data of area 0xc as it does once the ROM has
+// been written.
+const SHARED_AREA: usize = 0xc;
+
+/// Contents of one room.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RoomSpec {
+    pub warps: Vec<u8>,
+    pub enemies: Vec<u8>,
+    pub objects: Vec<TableEntry>,
+}
+
+/// Contents of one area.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AreaSpec {
+    /// Exactly [`ROOM_COUNT`] rooms.
+    pub rooms: Vec<RoomSpec>,
+    /// Exactly [`ROOM_COUNT`] bytes.
+    pub room_order: Vec<u8>,
+    /// Exactly [`CHEST_TABLE_LEN`] chests.
+    pub chests: Vec<Chest>,
+}
+
+impl Default for AreaSpec {
+    fn default() -> Self {
+        AreaSpec {
+            rooms: vec![RoomSpec::default(); ROOM_COUNT],
+            room_order: (0..ROOM_COUNT as u8).collect(),
+            chests: vec![
+                Chest {
+                    item_id: 0,
+                    arg: 1,
+                    text: 0,
+                    unknown: 0,
+                };
+                CHEST_TABLE_LEN
+            ],
+        }
+    }
+}
+
+/// Description of a synthetic ROM.
+///
+/// Starts with every area empty.  Areas, rooms, and chests are then
+/// replaced before calling [`RomBuilder::build`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomBuilder {
+    pub areas: Vec<AreaSpec>,
+    /// Bytes written right after the room data.
+    pub after_rooms: Vec<u8>,
+}
+
+impl Default for RomBuilder {
+    fn default() -> Self {
+        RomBuilder {
+            areas: vec![AreaSpec::default(); AREA_COUNT],
+            after_rooms: Vec::new(),
+        }
+    }
+}
+
+fn write_pointer(w: &mut Cursor<Vec<u8>>, offset: usize, target: u64) -> Result<(), Error> {
+    w.seek(SeekFrom::Start(offset as u64))?;
+    w.write_all(&util::rom_offset_to_pointer(target as u32)?)?;
+    Ok(())
+}
+
+// Writes an area's room pointers and rooms at the current position in the
+// same layout as `Neutopia::write_area`.
+fn write_area(w: &mut Cursor<Vec<u8>>, area: &AreaSpec) -> Result<(), Error> {
+    let room_ptrs_offset = w.position();
+    let mut room_offset = room_ptrs_offset + ROOM_COUNT as u64 * 3;
+
+    for (room_idx, room) in area.rooms.iter().enumerate() {
+        write_pointer(w, room_ptrs_offset as usize + room_idx * 3, room_offset)?;
+
+        let warp_table_ptr = room_offset + 3 * 3;
+        let enemy_table_ptr = warp_table_ptr + room.warps.len() as u64;
+        let object_table_ptr = enemy_table_ptr + room.enemies.len() as u64 + 1;
+        write_pointer(w, room_offset as usize, warp_table_ptr)?;
+        write_pointer(w, room_offset as usize + 3, enemy_table_ptr)?;
+        write_pointer(w, room_offset as usize + 6, object_table_ptr)?;
+
+        w.write_all(&room.warps)?;
+        w.write_all(&room.enemies)?;
+        w.write_all(&[0xff])?;
+        for object in &room.objects {
+            object.write(w)?;
+        }
+        w.write_all(&[0xff])?;
+        room_offset = w.position();
+    }
+
+    Ok(())
+}
+
+impl RomBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace room `room` of area `area`.
+    pub fn room(mut self, area: usize, room: usize, spec: RoomSpec) -> Self {
+        self.areas[area].rooms[room] = spec;
+        self
+    }
+
+    /// Write `data` right after the room data.
+    pub fn after_rooms(mut self, data: &[u8]) -> Self {
+        self.after_rooms = data.to_vec();
+        self
+    }
+
+    /// Replace chest `index` of area `area`.
+    pub fn chest(mut self, area: usize, index: usize, chest: Chest) -> Self {
+        self.areas[area].chests[index] = chest;
+        self
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.areas.len() != AREA_COUNT {
+            return Err(format_err!(
+                "{} areas given, need {}",
+                self.areas.len(),
+                AREA_COUNT
+            ));
+        }
+        for (area_idx, area) in self.areas.iter().enumerate() {
+            let counts = [
+                ("rooms", area.rooms.len(), ROOM_COUNT),
+                ("room order entries", area.room_order.len(), ROOM_COUNT),
+                ("chests", area.chests.len(), CHEST_TABLE_LEN),
+            ];
+            for (what, len, expected) in &counts {
+                if len != expected {
+                    return Err(format_err!(
+                        "area {:02x} has {} {}, need {}",
+                        area_idx,
+                        len,
+                        what,
+                        expected
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Build an un-headered ROM image.
+    pub fn build(&self) -> Result<Vec<u8>, Error> {
+        self.validate()?;
+        let mut w = Cursor::new(vec![0u8; bank::ROM_LEN]);
+
+        // Area 0x10 has its own room order table.
+        let room_orders = self
+            .areas
+            .iter()
+            .map(|a| &a.room_order)
+            .chain(Some(&self.areas[SHARED_AREA].room_order));
+        for (i, room_order) in room_orders.enumerate() {
+            let offset = ROOM_ORDER_TABLES + i * ROOM_COUNT;
+            write_pointer(&mut w, rommap::ROOM_ORDER_TABLE + i * 3, offset as u64)?;
+            w.seek(SeekFrom::Start(offset as u64))?;
+            w.write_all(room_order)?;
+        }
+
+        for (i, area) in self.areas.iter().enumerate() {
+            let offset = CHEST_TABLES + i * CHEST_TABLE_LEN * 4;
+            write_pointer(&mut w, rommap::CHEST_TABLE + i * 3, offset as u64)?;
+            w.seek(SeekFrom::Start(offset as u64))?;
+            for chest in &area.chests {
+                chest.write(&mut w)?;
+            }
+        }
+
+        w.seek(SeekFrom::Start(AREA_DATA as u64))?;
+        for area_idx in 0..AREA_COUNT {
+            let offset = w.position();
+            write_pointer(&mut w, rommap::AREA_TABLE + area_idx * 3, offset)?;
+            if area_idx == SHARED_AREA {
+                write_pointer(&mut w, rommap::AREA_TABLE + AREA_COUNT * 3, offset)?;
+            }
+            w.seek(SeekFrom::Start(offset))?;
+            write_area(&mut w, &self.areas[area_idx])?;
+        }
+        w.write_all(&self.after_rooms)?;
+
+        let data = w.into_inner();
+        if data.len() != bank::ROM_LEN {
+            return Err(format_err!("room data does not fit in the ROM"));
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rom::object::ObjectInfo, roundtrip, Neutopia, NeutopiaRom};
+
+    fn chest(item_id: u8) -> Chest {
+        Chest {
+            item_id,
+            arg: 1,
+            text: 0x85,
+            unknown: 0x41,
+        }
+    }
+
+    fn object(x: u8, y: u8, id: u8) -> TableEntry {
+        TableEntry::Object(ObjectInfo { x, y, id })
+    }
+
+    fn builder() -> RomBuilder {
+        RomBuilder::new()
+            .room(
+                4,
+                0x12,
+                RoomSpec {
+                    warps: vec![0x01, 0x02, 0x03, 0x04],
+                    enemies: vec![0x10, 0x11],
+                    objects: vec![object(2, 5, 0x4c), object(3, 5, 0x4d)],
+                },
+            )
+            .room(
+                0,
+                0x00,
+                RoomSpec {
+                    objects: vec![
+                        object(4, 4, 0x4c),
+                        TableEntry::Unknown0b([0x01, 0x02, 0x03]),
+                        object(4, 4, 0x20),
+                    ],
+                    ..Default::default()
+                },
+            )
+            .chest(4, 0, chest(0x11))
+            .chest(4, 1, chest(0x10))
+            .chest(0, 0, chest(0x02))
+    }
+
+    #[test]
+    fn test_build() {
+        let data = builder().build().unwrap();
+        assert_eq!(data.len(), bank::ROM_LEN);
+
+        let rom = NeutopiaRom::new(&data).unwrap();
+        assert_eq!(rom.area_pointers[0x10], rom.area_pointers[0xc]);
+        let room = &rom.room_info_tables[4][&0x12];
+        assert_eq!(room.warp_table, vec![0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(room.enemy_table, vec![0x10, 0x11]);
+
+        let n = Neutopia::new(&data).unwrap();
+        assert_eq!(n.areas.len(), AREA_COUNT);
+        assert_eq!(
+            n.areas[4].rooms[0x12].objects,
+            vec![object(2, 5, 0x4c), object(3, 5, 0x4d)]
+        );
+        // The conditional following the chest is split out of the room.
+        assert_eq!(n.areas[0].rooms[0].objects, vec![object(4, 4, 0x4c)]);
+        assert_eq!(n.conditionals[&chest(0x02)].data.len(), 2);
+
+        let chests = n.filter_chests(|c| c.area == 4).unwrap();
+        let items: Vec<u8> = chests.iter().map(|c| c.info.item_id).collect();
+        assert_eq!(items, vec![0x11, 0x10]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = builder().build().unwrap();
+        assert_eq!(roundtrip::round_trip(&data).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut b = RomBuilder::new();
+        b.areas[3].chests.pop();
+        assert_eq!(
+            b.build().unwrap_err().to_string(),
+            "area 03 has 7 chests, need 8"
+        );
+
+        let mut b = RomBuilder::new();
+        b.areas.pop();
+        assert!(b.build().is_err());
+
+        // 0x40 rooms of 0x200 byte warp tables in every area don't fit.
+        let mut b = RomBuilder::new();
+        for area in &mut b.areas {
+            for room in &mut area.rooms {
+                room.warps = vec![0; 0x200];
+            }
+        }
+        assert!(b.build().is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Writer for in-game dialog text.
+//!
+//! Dialog is written as printable ASCII terminated by [`TEXT_TERMINATOR`].
+//! Unused bytes in a slot are filled with the terminator so that shorter
+//! text never leaves fragments of the original dialog behind.
+//!
+//! This encoding, including the terminator and newline bytes, has not been
+//! checked against the game's dialog yet.
+
+use failure::{format_err, Error};
+use serde::{Deserialize, Serialize};
+
+/// Byte that marks the end of a dialog string.
+pub const TEXT_TERMINATOR: u8 = 0xff;
+
+/// Byte used to break a dialog string onto a new line.
+pub const TEXT_NEWLINE: u8 = 0xfe;
+
+/// A region of the ROM that holds a single dialog string.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TextSlot {
+    /// Human readable name of the slot (i.e. which NPC speaks it).
+    pub name: String,
+
+    /// ROM offset of the first byte of the slot.
+    pub offset: u32,
+
+    /// Number of bytes available in the slot, including the terminator.
+    pub len: usize,
+}
+
+/// Encode `text` into dialog bytes, including the trailing terminator.
+pub fn encode_text(text: &str) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    for c in text.chars() {
+        let b = match c {
+            '\n' => TEXT_NEWLINE,
+            ' '..='~' => c as u8,
+            _ => return Err(format_err!("can't encode character {:?} in {:?}", c, text)),
+        };
+        data.push(b);
+    }
+    data.push(TEXT_TERMINATOR);
+
+    Ok(data)
+}
+
+/// Write `text` into `slot` of `rom`.
+pub fn write_text(rom: &mut [u8], slot: &TextSlot, text: &str) -> Result<(), Error> {
+    let data = encode_text(text)?;
+    if data.len() > slot.len {
+        return Err(format_err!(
+            "text {:?} is {} bytes, too long for slot {} ({} bytes)",
+            text,
+            data.len(),
+            slot.name,
+            slot.len
+        ));
+    }
+
+    let start = slot.offset as usize;
+    let end = start + slot.len;
+    let dest = rom.get_mut(start..end).ok_or_else(|| {
+        format_err!(
+            "slot {} ({:05x}-{:05x}) is outside of the rom",
+            slot.name,
+            start,
+            end
+        )
+    })?;
+
+    for b in dest.iter_mut() {
+        *b = TEXT_TERMINATOR;
+    }
+    dest[..data.len()].copy_from_slice(&data);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_text() {
+        assert_eq!(
+            encode_text("Hi!\nYo").unwrap(),
+            vec![b'H', b'i', b'!', TEXT_NEWLINE, b'Y', b'o', TEXT_TERMINATOR]
+        );
+        assert!(encode_text("caf\u{e9}").is_err());
+    }
+
+    #[test]
+    fn test_write_text() {
+        let mut rom = vec![0u8; 8];
+        let slot = TextSlot {
+            name: "test".into(),
+            offset: 2,
+            len: 4,
+        };
+        write_text(&mut rom, &slot, "ab").unwrap();
+        assert_eq!(rom, vec![0, 0, b'a', b'b', 0xff, 0xff, 0, 0]);
+
+        assert!(write_text(&mut rom, &slot, "abcd").is_err());
+    }
+}
@@ -1,29 +1,44 @@
 use failure::{format_err, Error};
 
+use super::bank::Address;
+
+// Data pointers map their bank into page 2 ($4000-$5fff).
+const POINTER_PAGE: u8 = 2;
+
+// Banks in the first 256K are written using their $20-$3f mirror.
+const POINTER_MIRROR: u8 = 0x20;
+
+/// Decode a 3 byte bank, address pointer into a ROM offset.
 pub fn pointer_to_rom_offset(data: &[u8]) -> Result<u32, Error> {
-    assert!(data.len() >= 3);
+    if data.len() < 3 {
+        return Err(format_err!(
+            "pointer needs 3 bytes, only {} available",
+            data.len()
+        ));
+    }
 
-    let value = ((data[0] as u32) << 13) | ((data[2] as u32 & 0x1f) << 8) | (data[1] as u32);
-    if value < 0x40000 {
-        Err(format_err!(
-            "can't convert: {:02x} {:02x} {:02x}",
+    let address = Address::new(data[0], u16::from_le_bytes([data[1], data[2]]));
+    let offset = address.offset().map_err(|e| {
+        format_err!(
+            "can't convert {:02x} {:02x} {:02x}: {}",
             data[0],
             data[1],
-            data[2]
-        ))
-    } else {
-        Ok(value - 0x40000)
-    }
+            data[2],
+            e
+        )
+    })?;
+    Ok(offset as u32)
 }
 
-pub fn rom_offset_to_pointer(offset: u32) -> [u8; 3] {
-    let addr = offset + 0x40000;
+/// Encode a ROM offset as a 3 byte bank, address pointer.
+pub fn rom_offset_to_pointer(offset: u32) -> Result<[u8; 3], Error> {
+    let mut address = Address::from_offset(offset as usize, POINTER_PAGE)?;
+    if address.bank < POINTER_MIRROR {
+        address.bank += POINTER_MIRROR;
+    }
+    let [lo, hi] = address.address.to_le_bytes();
 
-    [
-        (addr >> 13) as u8,
-        (addr & 0xff) as u8,
-        (((addr >> 8) & 0x1f) as u8) | 0x40,
-    ]
+    Ok([address.bank, lo, hi])
 }
 
 pub fn decode_pointer_table(data: &[u8], entries: usize) -> Result<Vec<u32>, Error> {
@@ -38,26 +53,50 @@ pub fn decode_pointer_table(data: &[u8], entries: usize) -> Result<Vec<u32>, Err
     let mut table = Vec::new();
 
     for i in 0..entries {
-        let pointer = pointer_to_rom_offset(&data[(i * 3)..])?;
+        let pointer = pointer_to_rom_offset(&data[(i * 3)..])
+            .map_err(|e| format_err!("entry {}: {}", i, e))?;
         table.push(pointer);
     }
 
     Ok(table)
 }
 
-pub fn read_object_table(data: &[u8]) -> Vec<u8> {
-    let mut table = Vec::new();
-    let mut i = 0;
-    loop {
-        let val = data[i];
-        if val == 0xff {
-            break;
-        }
-        table.push(val);
+/// Returns `data[start..]` or an error if `start` is past the end of `data`.
+pub fn slice_from(data: &[u8], start: usize) -> Result<&[u8], Error> {
+    data.get(start..).ok_or_else(|| {
+        format_err!(
+            "offset {:05x} is past the end of the {:05x} byte ROM",
+            start,
+            data.len()
+        )
+    })
+}
+
+/// Returns `data[start..end]` or an error if the range is inverted or past
+/// the end of `data`.
+pub fn slice(data: &[u8], start: usize, end: usize) -> Result<&[u8], Error> {
+    if start > end {
+        return Err(format_err!("range {:05x}-{:05x} is inverted", start, end));
+    }
+    data.get(start..end).ok_or_else(|| {
+        format_err!(
+            "range {:05x}-{:05x} is past the end of the {:05x} byte ROM",
+            start,
+            end,
+            data.len()
+        )
+    })
+}
 
-        i += 1;
+/// Reads a table terminated by `0xff`, not including the terminator.
+pub fn read_object_table(data: &[u8]) -> Result<Vec<u8>, Error> {
+    match data.iter().position(|b| *b == 0xff) {
+        Some(len) => Ok(data[..len].to_vec()),
+        None => Err(format_err!(
+            "table is missing its ff terminator after {} bytes",
+            data.len()
+        )),
     }
-    table
 }
 
 #[cfg(test)]
@@ -67,5 +106,53 @@ mod tests {
     fn test_pointer_to_rom_offset() {
         assert_eq!(pointer_to_rom_offset(&[0x48, 0x4e, 0x45]).unwrap(), 0x5054e);
         assert_eq!(pointer_to_rom_offset(&[0x49, 0x44, 0x51]).unwrap(), 0x53144);
+        assert!(pointer_to_rom_offset(&[0x48, 0x4e]).is_err());
+        assert!(pointer_to_rom_offset(&[0x80, 0x00, 0x40]).is_err());
+    }
+
+    #[test]
+    fn test_decode_pointer_table() {
+        let data = [0x48, 0x4e, 0x45, 0x49, 0x44, 0x51, 0x80, 0x00, 0x40];
+        assert_eq!(
+            decode_pointer_table(&data, 2).unwrap(),
+            vec![0x5054e, 0x53144]
+        );
+        assert!(decode_pointer_table(&data, 4).is_err());
+        assert_eq!(
+            decode_pointer_table(&data, 3).unwrap_err().to_string(),
+            "entry 2: can't convert 80 00 40: bank 80 is not a ROM bank"
+        );
+    }
+
+    #[test]
+    fn test_slice() {
+        let data = [0u8, 1, 2, 3];
+        assert_eq!(slice(&data, 1, 3).unwrap(), &[1, 2]);
+        assert_eq!(slice(&data, 4, 4).unwrap(), &[]);
+        assert!(slice(&data, 3, 1).is_err());
+        assert!(slice(&data, 2, 5).is_err());
+        assert_eq!(slice_from(&data, 3).unwrap(), &[3]);
+        assert!(slice_from(&data, 5).is_err());
+    }
+
+    #[test]
+    fn test_read_object_table() {
+        assert_eq!(read_object_table(&[1, 2, 0xff, 3]).unwrap(), vec![1, 2]);
+        assert_eq!(read_object_table(&[0xff]).unwrap(), vec![]);
+        assert!(read_object_table(&[1, 2]).is_err());
+        assert!(read_object_table(&[]).is_err());
+    }
+
+    #[test]
+    fn test_rom_offset_to_pointer() {
+        assert_eq!(rom_offset_to_pointer(0x5054e).unwrap(), [0x48, 0x4e, 0x45]);
+        assert_eq!(rom_offset_to_pointer(0x53144).unwrap(), [0x49, 0x44, 0x51]);
+        assert_eq!(rom_offset_to_pointer(0x10000).unwrap(), [0x28, 0x00, 0x40]);
+        assert!(rom_offset_to_pointer(0x60000).is_err());
+
+        for offset in (0..crate::bank::ROM_LEN as u32).step_by(0x777) {
+            let pointer = rom_offset_to_pointer(offset).unwrap();
+            assert_eq!(pointer_to_rom_offset(&pointer).unwrap(), offset);
+        }
     }
 }
@@ -0,0 +1,186 @@
+//! Checks that the data model can be written to the ROM as is.
+//!
+//! Object positions are stored as a nibble each, so anything outside the
+//! 16x16 grid would silently wrap when written.  Chest objects must refer
+//! to an entry in their area's chest table.  Room terrain hasn't been
+//! located in the ROM so positions inside walls aren't caught.
+
+use std::error;
+use std::fmt;
+
+use super::{Area, Neutopia};
+
+/// Object positions are one nibble each.
+pub const ROOM_CELLS: u8 = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problem {
+    OutOfBounds { x: u8, y: u8 },
+    MissingChest { id: u8, chests: usize },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OutOfBounds { x, y } => write!(f, "position ({}, {}) is outside the room", x, y),
+            Self::MissingChest { id, chests } => write!(f, "references chest {} of {}", id, chests),
+        }
+    }
+}
+
+/// A problem with one object table entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    pub area: u8,
+    pub room: u8,
+    /// Index of the entry in the room's object table.
+    pub entry: usize,
+    pub problem: Problem,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "room {:02x}:{:02x} entry {}: {}",
+            self.area, self.room, self.entry, self.problem
+        )
+    }
+}
+
+impl error::Error for ValidationError {}
+
+/// Every problem found, returned as an error by [`Neutopia::write`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let errors: Vec<String> = self.0.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", errors.join("; "))
+    }
+}
+
+impl error::Error for ValidationErrors {}
+
+/// Returns the problems with the object tables of area `area_idx`.
+pub fn validate_area(area_idx: u8, area: &Area) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    for (room_idx, room) in area.rooms.iter().enumerate() {
+        for (entry_idx, entry) in room.objects.iter().enumerate() {
+            let mut error = |problem| {
+                errors.push(ValidationError {
+                    area: area_idx,
+                    room: room_idx as u8,
+                    entry: entry_idx,
+                    problem,
+                })
+            };
+            if let Some(info) = entry.object_info() {
+                if info.x >= ROOM_CELLS || info.y >= ROOM_CELLS {
+                    error(Problem::OutOfBounds {
+                        x: info.x,
+                        y: info.y,
+                    });
+                }
+            }
+            match entry.chest_id() {
+                Some(id) if id as usize >= area.chest_table.len() => {
+                    error(Problem::MissingChest {
+                        id,
+                        chests: area.chest_table.len(),
+                    });
+                }
+                _ => (),
+            }
+        }
+    }
+    errors
+}
+
+impl Neutopia {
+    /// Returns every problem that would keep the data from being written
+    /// correctly.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        self.areas
+            .iter()
+            .enumerate()
+            .flat_map(|(area_idx, area)| validate_area(area_idx as u8, area))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom::object::{ObjectInfo, TableEntry};
+    use crate::synthetic::RomBuilder;
+
+    #[test]
+    fn test_validate() {
+        let mut n = Neutopia::new(&RomBuilder::new().build().unwrap()).unwrap();
+        assert_eq!(n.validate(), vec![]);
+
+        let objects = &mut n.areas[4].rooms[0x12].objects;
+        objects.push(TableEntry::Object(ObjectInfo {
+            x: 15,
+            y: 15,
+            id: 0x4c,
+        }));
+        objects.push(TableEntry::Burnable(ObjectInfo {
+            x: 16,
+            y: 2,
+            id: 0x10,
+        }));
+        objects.push(TableEntry::Object(ObjectInfo {
+            x: 1,
+            y: 1,
+            id: 0x53,
+        }));
+        n.areas[4].chest_table.truncate(7);
+        n.areas[6].rooms[0]
+            .objects
+            .push(TableEntry::Swords(ObjectInfo {
+                x: 0,
+                y: 0x20,
+                id: 0x01,
+            }));
+
+        let errors = n.validate();
+        assert_eq!(
+            errors,
+            vec![
+                ValidationError {
+                    area: 4,
+                    room: 0x12,
+                    entry: 1,
+                    problem: Problem::OutOfBounds { x: 16, y: 2 },
+                },
+                ValidationError {
+                    area: 4,
+                    room: 0x12,
+                    entry: 2,
+                    problem: Problem::MissingChest { id: 7, chests: 7 },
+                },
+                ValidationError {
+                    area: 6,
+                    room: 0,
+                    entry: 0,
+                    problem: Problem::OutOfBounds { x: 0, y: 0x20 },
+                },
+            ]
+        );
+
+        let err = n.write().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ValidationErrors>(),
+            Some(&ValidationErrors(errors))
+        );
+        assert_eq!(
+            err.to_string(),
+            "room 04:12 entry 1: position (16, 2) is outside the room; \
+             room 04:12 entry 2: references chest 7 of 7; \
+             room 06:00 entry 0: position (0, 32) is outside the room"
+        );
+    }
+}
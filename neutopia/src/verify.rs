@@ -3,6 +3,8 @@ use std::collections::HashMap;
 use failure::{format_err, Error};
 use lazy_static::lazy_static;
 
+use super::bank;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Region {
     NA,
@@ -57,11 +59,11 @@ lazy_static! {
 }
 
 pub fn verify(data: &[u8]) -> Result<RomInfo, Error> {
-    let expected_size = 384 * 1024;
-    let header_size = 0x200;
+    let expected_size = bank::ROM_LEN;
+    let header_size = bank::HEADER_LEN;
 
     let (headered, buffer) = if data.len() == expected_size {
-        (false, &data as &[u8])
+        (false, data)
     } else if data.len() == expected_size + header_size {
         (true, &data[header_size..])
     } else {
@@ -84,3 +86,28 @@ pub fn verify(data: &[u8]) -> Result<RomInfo, Error> {
         region: db_entry.region,
     })
 }
+
+/// Like [`verify`] but fails unless `data` is a known dump.
+pub fn verify_known(data: &[u8]) -> Result<RomInfo, Error> {
+    let info = verify(data)?;
+    if !info.known {
+        return Err(format_err!(
+            "Rom with MD5 hash {} is unrecognized.",
+            &info.md5_hash
+        ));
+    }
+    Ok(info)
+}
+
+/// Like [`verify_known`] but also fails unless `data` is the NA release,
+/// the only one the randomizer and its patches target.
+pub fn verify_supported(data: &[u8]) -> Result<RomInfo, Error> {
+    let info = verify_known(data)?;
+    if info.region != Region::NA {
+        return Err(format_err!(
+            "Region {:?} rom not supported.  Please use NA rom.",
+            &info.region
+        ));
+    }
+    Ok(info)
+}
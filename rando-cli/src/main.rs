@@ -5,7 +5,7 @@ use std::path::PathBuf;
 use failure::Error;
 use structopt::StructOpt;
 
-use rando::RandoType;
+use rando::{EquipmentMode, OutputFormat, RandoType};
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "basic")]
@@ -19,13 +19,63 @@ struct Opt {
     #[structopt(long)]
     seed: Option<String>,
 
+    /// Output format: rom, or an ips or bps patch against the input ROM.
+    #[structopt(long, default_value = "rom")]
+    format: OutputFormat,
+
     #[structopt(long = "type", default_value = "local")]
     ty: RandoType,
+
+    /// Equipment behavior: vanilla, no-downgrade, or progressive.
+    #[structopt(long, default_value = "no-downgrade")]
+    equipment: EquipmentMode,
+
+    /// Apply an optional patch that is off by default.  May be repeated.
+    #[structopt(long = "patch")]
+    enable_patches: Vec<String>,
+
+    /// Skip an optional patch that is on by default.  May be repeated.
+    #[structopt(long = "no-patch")]
+    disable_patches: Vec<String>,
+
+    /// Apply patches even if they modify the same bytes.
+    #[structopt(long)]
+    allow_patch_conflicts: bool,
+
+    /// List the optional patches and exit.
+    #[structopt(long)]
+    list_patches: bool,
+}
+
+fn list_patches() {
+    for patch in rando::patches::PATCHES.iter().filter(|p| p.optional) {
+        println!(
+            "{:20} {} {}",
+            patch.name,
+            if patch.default {
+                "(default)"
+            } else {
+                "         "
+            },
+            patch.description
+        );
+    }
 }
 
 fn main() -> Result<(), Error> {
     let opt = Opt::from_args();
 
+    if opt.list_patches {
+        list_patches();
+        return Ok(());
+    }
+
+    let mut patches = rando::patches::default_patches();
+    patches.extend(opt.enable_patches.iter().cloned());
+    for name in &opt.disable_patches {
+        patches.remove(name);
+    }
+
     let mut f = File::open(&opt.rom)?;
     let mut buffer = Vec::new();
     f.read_to_end(&mut buffer)?;
@@ -33,16 +83,24 @@ fn main() -> Result<(), Error> {
     let config = rando::Config {
         seed: opt.seed,
         ty: opt.ty,
+        equipment: opt.equipment,
+        patches,
+        allow_patch_conflicts: opt.allow_patch_conflicts,
     };
 
     let r = rando::randomize(&config, &buffer)?;
 
-    let filename = &opt
-        .out
-        .unwrap_or_else(|| PathBuf::from(format!("neutopia-randomizer-{}.pce", r.seed)));
+    let format = opt.format;
+    let filename = &opt.out.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "neutopia-randomizer-{}.{}",
+            r.seed,
+            format.extension()
+        ))
+    });
 
     let mut f = File::create(filename)?;
-    f.write_all(&r.data)?;
+    f.write_all(&r.output(&buffer, format)?)?;
 
     println!("wrote {}", filename.display());
 
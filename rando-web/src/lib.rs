@@ -1,5 +1,7 @@
 #![recursion_limit = "512"]
 
+use std::collections::BTreeSet;
+
 use regex::Regex;
 use wasm_bindgen::prelude::*;
 use yew::services::reader::{File, FileData, ReaderService, ReaderTask};
@@ -7,7 +9,7 @@ use yew::web_sys::Blob;
 use yew::{html, prelude::*, ChangeData, Component, ComponentLink, Html, ShouldRender};
 
 use neutopia::verify;
-use rando::{randomize, Config, RandoType};
+use rando::{patches, randomize, Config, EquipmentMode, RandoType};
 
 mod info;
 
@@ -25,7 +27,7 @@ fn parse_semver(semver: &str) -> WebsiteMode {
     let captures = re.captures(semver);
     match captures {
         Some(c) => {
-            if let Some(_) = c.get(2) {
+            if c.get(2).is_some() {
                 WebsiteMode::Beta
             } else {
                 WebsiteMode::Release
@@ -46,11 +48,13 @@ struct Model {
     tasks: Vec<ReaderTask>,
 
     verified_str: String,
+    patches: BTreeSet<String>,
 }
 
 enum Msg {
     File(Option<File>),
     Loaded(FileData),
+    TogglePatch(&'static str),
 }
 
 impl Model {
@@ -72,6 +76,18 @@ impl Model {
         }
     }
 
+    fn view_patch_option(&self, patch: &patches::PatchInfo) -> Html {
+        let name = patch.name;
+        html! {
+            <label class="panel-block" title=patch.description>
+                <input type="checkbox"
+                    checked=self.patches.contains(name)
+                    onclick=self.link.callback(move |_| Msg::TogglePatch(name))/>
+                { name }
+            </label>
+        }
+    }
+
     fn view_randomizer(&self) -> Html {
         html! {
             <>
@@ -80,9 +96,10 @@ impl Model {
                     <p class="panel-heading">
                        {"Generate Seed"}
                     </p>
-                    <div class="panel-block">
-                        {"Options will go here"}
-                    </div>
+                    { for patches::PATCHES
+                        .iter()
+                        .filter(|p| p.optional)
+                        .map(|p| self.view_patch_option(p)) }
                     <div class="panel-block">
                         <div class="file">
                             <span class="file-cta">
@@ -120,6 +137,7 @@ impl Component for Model {
             reader: ReaderService::new(),
             tasks: vec![],
             verified_str: "".into(),
+            patches: patches::default_patches(),
             link,
         }
     }
@@ -140,18 +158,23 @@ impl Component for Model {
                         let config = Config {
                             ty: RandoType::Global,
                             seed: None,
+                            equipment: EquipmentMode::NoDowngrade,
+                            patches: self.patches.clone(),
+                            allow_patch_conflicts: false,
                         };
                         let game = randomize(&config, &file.content).unwrap();
 
-                        saveRom(
-                            &game.data,
-                            format!("neutopia-randomizer-{}.pce", game.seed).into(),
-                        );
+                        saveRom(&game.data, format!("neutopia-randomizer-{}.pce", game.seed));
                         format!("{:?}", &info)
                     }
                     Err(e) => format!("invalid rom: {}", e),
                 }
             }
+            Msg::TogglePatch(name) => {
+                if !self.patches.remove(name) {
+                    self.patches.insert(name.to_string());
+                }
+            }
         }
         true
     }
@@ -1,44 +1,12 @@
 use std::env;
-use std::fs;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
 
 use failure::{format_err, Error};
 
-#[cfg(target_os = "macos")]
-fn os_type() -> &'static str {
-    "macos"
-}
-
-#[cfg(target_os = "windows")]
-fn os_type() -> &'static str {
-    "windows"
-}
-
-#[cfg(target_os = "linux")]
-fn os_type() -> &'static str {
-    "linux"
-}
-
-#[cfg(target_os = "windows")]
-fn exe_suffix() -> &'static str {
-    ".exe"
-}
-
-#[cfg(not(target_os = "windows"))]
-fn exe_suffix() -> &'static str {
-    ""
-}
-
-fn bass_path() -> PathBuf {
-    PathBuf::from("../build/bin")
-        .join(os_type())
-        .join(format!("bass{}", exe_suffix()))
-}
-
-fn handle_asm(path: &PathBuf) -> Result<(), Error> {
-    let bass = bass_path();
+fn handle_asm(path: &Path, symbols_rs: &mut impl Write) -> Result<(), Error> {
     let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-    let tmp_dir = out_path.join("asm_build");
 
     let ips = path.with_extension("ips");
     let ips = ips.file_name().unwrap();
@@ -51,15 +19,26 @@ fn handle_asm(path: &PathBuf) -> Result<(), Error> {
             e
         )
     })?;
-    fs::create_dir_all(&tmp_dir)
-        .map_err(|e| format_err!("unable to create dir {}: {}", tmp_dir.to_string_lossy(), e))?;
-    asm_build::build(&bass, 0x60000, &tmp_dir, &[path.clone()], &ips)
+    let symbols = asm_build::build(&[path.to_path_buf()], &ips, None)
         .map_err(|e| format_err!("asm_build failed: {}", e))?;
+
+    let mut json = File::create(ips.with_extension("json"))?;
+    asm_build::write_symbols_json(&mut json, &symbols)?;
+
+    let module = path.file_stem().unwrap().to_string_lossy();
+    asm_build::write_symbols_rust(symbols_rs, &module, &symbols)?;
+
     Ok(())
 }
 
 fn main() -> Result<(), Error> {
     let asm_src_dir = PathBuf::from("src").join("asm");
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::create_dir_all(out_path.join("asm"))?;
+    let mut symbols_rs = File::create(out_path.join("asm").join("symbols.rs"))?;
+    writeln!(symbols_rs, "// Generated by rando/build.rs.  Do not edit.")?;
+
+    let mut paths = Vec::new();
     println!("cargo:rerun-if-changed={}", asm_src_dir.to_string_lossy());
     for entry in fs::read_dir(&asm_src_dir)? {
         let path = entry?.path();
@@ -67,11 +46,17 @@ fn main() -> Result<(), Error> {
             if let Some(ext) = path.extension() {
                 if ext == "asm" {
                     println!("cargo:rerun-if-changed={}", &path.to_string_lossy());
-                    handle_asm(&path)?;
+                    paths.push(path);
                 }
             }
         }
     }
 
+    // Sort so the generated symbols module is stable.
+    paths.sort();
+    for path in &paths {
+        handle_asm(path, &mut symbols_rs)?;
+    }
+
     Ok(())
 }
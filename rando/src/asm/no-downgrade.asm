@@ -1,17 +1,12 @@
 arch pce.cpu
 
-macro seek(variable offset) {
-  origin (offset - $6000)
-  base offset
-}
-
-seek($ddf1);
+bank $03, $ddf1
 get_sword_handler:
     dw handle_upgradeable_item 
     dw handle_upgradeable_item
     dw handle_upgradeable_item
 
-seek($df52);
+bank $03, $df52
 handle_upgradeable_item:
     ldx  $35d0 // chest item id
     lda  $35d1 // chest arg
@@ -1,17 +1,12 @@
 arch pce.cpu
 
-macro seek(variable offset) {
-  origin (offset - $6000)
-  base offset
-}
-
-seek($ddf1);
+bank $03, $ddf1
 get_sword_handler:
     dw handle_progressive_item 
     dw handle_progressive_item
     dw handle_progressive_item
 
-seek($df52);
+bank $03, $df52
 handle_progressive_item:
     ldx  $35d0
     inc  $2e44,x
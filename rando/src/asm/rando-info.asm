@@ -0,0 +1,7 @@
+arch pce.cpu
+
+// Unused space ahead of the relocated chest tables where the randomizer
+// records how the ROM was generated.  Filled in by
+// `rando::patches::write_applied`.
+origin $4fe00
+    slot rando_info, $80
@@ -0,0 +1,275 @@
+use std::collections::BTreeSet;
+
+use failure::{format_err, Error};
+use neutopia::{
+    rom,
+    text::{self, TextSlot},
+    Ledger,
+};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{Placement, State};
+
+static HINT_SLOTS_DATA: &[u8] = include_bytes!("hint_slots.json");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum HintType {
+    /// Names an area that holds an item required to finish the game.
+    WayOfTheHero,
+    /// Names an area that holds no required items.
+    Barren,
+    /// Names the item found at a specific check.
+    ItemAtLocation,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct HintConfig {
+    pub(crate) count: usize,
+    pub(crate) types: Vec<HintType>,
+}
+
+// No dialog slots for hints have been located yet (`hint_slots.json` is
+// empty) so seeds don't get hints.
+impl Default for HintConfig {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            types: vec![
+                HintType::WayOfTheHero,
+                HintType::Barren,
+                HintType::ItemAtLocation,
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Hint {
+    pub(crate) ty: HintType,
+    pub(crate) text: String,
+}
+
+fn is_required(placement: &Placement) -> bool {
+    State::is_progression(&placement.item)
+}
+
+fn way_of_the_hero_hints(placements: &[Placement]) -> Vec<String> {
+    let areas: BTreeSet<u8> = placements
+        .iter()
+        .filter(|p| is_required(p))
+        .map(|p| p.check.area)
+        .collect();
+
+    areas
+        .iter()
+        .map(|area| {
+            format!(
+                "The way of the hero\npasses through\n{}.",
+                neutopia::area_name(*area)
+            )
+        })
+        .collect()
+}
+
+fn barren_hints(placements: &[Placement]) -> Vec<String> {
+    let all_areas: BTreeSet<u8> = placements.iter().map(|p| p.check.area).collect();
+    let required_areas: BTreeSet<u8> = placements
+        .iter()
+        .filter(|p| is_required(p))
+        .map(|p| p.check.area)
+        .collect();
+
+    all_areas
+        .difference(&required_areas)
+        .map(|area| {
+            format!(
+                "Nothing of value\nwaits in\n{}.",
+                neutopia::area_name(*area)
+            )
+        })
+        .collect()
+}
+
+fn item_at_location_hints(placements: &[Placement]) -> Vec<String> {
+    placements
+        .iter()
+        // Bombs and medicine are too common to be worth a hint.
+        .filter(|p| !matches!(p.item.item(), rom::Item::Bombs(_) | rom::Item::Medicine))
+        .map(|p| format!("{}\nholds the\n{}.", p.check.name, p.item.get_item_name()))
+        .collect()
+}
+
+/// Generate hints from the final item placements.
+///
+/// Hint types are handed out round robin from `config.types`.  Types that
+/// run out of candidates are skipped so fewer than `config.count` hints may
+/// be returned.
+pub(crate) fn generate_hints(
+    rng: &mut impl Rng,
+    config: &HintConfig,
+    placements: &[Placement],
+) -> Vec<Hint> {
+    let mut candidates: Vec<(HintType, Vec<String>)> = config
+        .types
+        .iter()
+        .map(|ty| {
+            let mut texts = match ty {
+                HintType::WayOfTheHero => way_of_the_hero_hints(placements),
+                HintType::Barren => barren_hints(placements),
+                HintType::ItemAtLocation => item_at_location_hints(placements),
+            };
+            texts.shuffle(rng);
+            (*ty, texts)
+        })
+        .collect();
+
+    let mut hints = Vec::new();
+    let mut i = 0;
+    while hints.len() < config.count && candidates.iter().any(|(_, t)| !t.is_empty()) {
+        let idx = i % candidates.len();
+        let (ty, texts) = &mut candidates[idx];
+        if let Some(text) = texts.pop() {
+            hints.push(Hint { ty: *ty, text });
+        }
+        i += 1;
+    }
+
+    hints
+}
+
+fn get_hint_slots() -> Result<Vec<TextSlot>, Error> {
+    serde_json::from_slice(HINT_SLOTS_DATA)
+        .map_err(|e| format_err!("failed to parse hint slots JSON: {}", e))
+}
+
+/// Write `hints` into the dialog slots listed in `hint_slots.json`.
+///
+/// Fails if there are more hints than slots rather than handing out hints
+/// that never make it into the game.
+pub(crate) fn write_hints(
+    rom: &mut [u8],
+    hints: &[Hint],
+    ledger: &mut Ledger,
+) -> Result<(), Error> {
+    let slots = get_hint_slots()?;
+    if hints.len() > slots.len() {
+        return Err(format_err!(
+            "can't place {} hints, only {} hint slots are known",
+            hints.len(),
+            slots.len()
+        ));
+    }
+    for (slot, hint) in slots.iter().zip(hints.iter()) {
+        let start = slot.offset as usize;
+        ledger.claim(&format!("hint {}", slot.name), start, start + slot.len)?;
+        text::write_text(rom, slot, &hint.text)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Check;
+    use rand_core::SeedableRng;
+    use rand_pcg::Pcg32;
+
+    fn placement(area: u8, room: u8, item_id: u8) -> Placement {
+        Placement {
+            check: Check {
+                name: format!("Check {:x}:{:x}", area, room),
+                area,
+                room,
+                index: 0,
+                gates: Vec::new(),
+            },
+            item: rom::Chest {
+                item_id,
+                arg: 1,
+                text: 0,
+                unknown: 0,
+            },
+        }
+    }
+
+    fn placements() -> Vec<Placement> {
+        vec![
+            placement(0x4, 0x01, 0x02),
+            placement(0x4, 0x02, 0x00),
+            placement(0x5, 0x01, 0x01),
+            placement(0x6, 0x01, 0x0b),
+        ]
+    }
+
+    #[test]
+    fn test_hint_candidates() {
+        let placements = placements();
+        assert_eq!(way_of_the_hero_hints(&placements).len(), 2);
+        assert_eq!(
+            barren_hints(&placements),
+            vec!["Nothing of value\nwaits in\nCrypt 2.".to_string()]
+        );
+        assert_eq!(item_at_location_hints(&placements).len(), 2);
+    }
+
+    #[test]
+    fn test_generate_hints_is_deterministic() {
+        let placements = placements();
+        let config = HintConfig {
+            count: 4,
+            ..Default::default()
+        };
+
+        let a = generate_hints(&mut Pcg32::seed_from_u64(1), &config, &placements);
+        let b = generate_hints(&mut Pcg32::seed_from_u64(1), &config, &placements);
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 4);
+        assert_eq!(a[0].ty, HintType::WayOfTheHero);
+        assert_eq!(a[1].ty, HintType::Barren);
+        assert_eq!(a[2].ty, HintType::ItemAtLocation);
+    }
+
+    #[test]
+    fn test_generate_hints_respects_types() {
+        let placements = placements();
+        let config = HintConfig {
+            count: 10,
+            types: vec![HintType::Barren],
+        };
+
+        let hints = generate_hints(&mut Pcg32::seed_from_u64(1), &config, &placements);
+        assert_eq!(hints.len(), 1);
+        assert_eq!(hints[0].ty, HintType::Barren);
+    }
+
+    #[test]
+    fn test_hint_slots_parse() {
+        get_hint_slots().unwrap();
+    }
+
+    #[test]
+    fn test_write_hints_without_slots() {
+        let slots = get_hint_slots().unwrap().len();
+        let hints = vec![
+            Hint {
+                ty: HintType::Barren,
+                text: "Nothing of value".to_string(),
+            };
+            slots + 1
+        ];
+        let mut rom = vec![0u8; 0x60000];
+        let err = write_hints(&mut rom, &hints, &mut Ledger::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "can't place {} hints, only {} hint slots are known",
+                slots + 1,
+                slots
+            )
+        );
+        write_hints(&mut rom, &[], &mut Ledger::new()).unwrap();
+    }
+}
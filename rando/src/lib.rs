@@ -1,19 +1,21 @@
+use std::collections::BTreeSet;
 use std::io::{prelude::*, Cursor, SeekFrom};
 use std::str::FromStr;
 
 use failure::{format_err, Error};
-use ips::Patch;
-use neutopia::{self, rom, verify::Region, Neutopia};
+use neutopia::{self, bank, bps, rom, Ledger, Neutopia, NeutopiaRom};
 use radix_fmt::radix_36;
 use rand::{self, prelude::*};
 use rand_core::SeedableRng;
 use rand_pcg::Pcg32;
 
-mod patches;
+mod hints;
+pub mod patches;
 mod state;
 
-pub use state::Check;
+use hints::HintConfig;
 use state::State;
+pub use state::{Check, Placement};
 
 #[derive(Debug)]
 pub enum RandoType {
@@ -34,10 +36,73 @@ impl FromStr for RandoType {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EquipmentMode {
+    /// Equipment behaves as in the original game.  Picking up a lower tier
+    /// item replaces a higher tier one.
+    Vanilla,
+    /// Lower tier equipment never replaces higher tier equipment.
+    NoDowngrade,
+    /// Each sword, armor, or shield picked up upgrades the current one by a
+    /// tier regardless of which item the chest holds.
+    Progressive,
+}
+
+impl FromStr for EquipmentMode {
+    type Err = Error;
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "vanilla" => Ok(EquipmentMode::Vanilla),
+            "no-downgrade" => Ok(EquipmentMode::NoDowngrade),
+            "progressive" => Ok(EquipmentMode::Progressive),
+            _ => Err(format_err!("Could not parse equipment mode")),
+        }
+    }
+}
+
+/// How a randomized game is written out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// The full randomized ROM.
+    Rom,
+    /// An IPS patch against the verified base ROM.
+    Ips,
+    /// A BPS patch against the verified base ROM.
+    Bps,
+}
+
+impl OutputFormat {
+    /// File extension for files in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Rom => "pce",
+            OutputFormat::Ips => "ips",
+            OutputFormat::Bps => "bps",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "rom" => Ok(OutputFormat::Rom),
+            "ips" => Ok(OutputFormat::Ips),
+            "bps" => Ok(OutputFormat::Bps),
+            _ => Err(format_err!("Could not parse output format")),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {
     pub ty: RandoType,
     pub seed: Option<String>,
+    pub equipment: EquipmentMode,
+    /// Names of the optional patches to apply.  See [`patches::PATCHES`].
+    pub patches: BTreeSet<String>,
+    /// Apply patches even if more than one of them writes the same bytes.
+    pub allow_patch_conflicts: bool,
 }
 
 pub struct RandomizedGame {
@@ -45,8 +110,38 @@ pub struct RandomizedGame {
     pub data: Vec<u8>,
 }
 
+impl RandomizedGame {
+    /// Create a BPS patch that turns the verified base ROM `base` into this
+    /// game.
+    ///
+    /// `base` may be headered.  The patch is always made against the
+    /// un-headered ROM.
+    pub fn bps_patch(&self, base: &[u8]) -> Result<Vec<u8>, Error> {
+        let base = verify_rom(base.to_vec())?;
+        let metadata = format!("neutopia-randomizer seed {}", self.seed);
+        Ok(bps::create_patch(&base, &self.data, &metadata))
+    }
+
+    /// Create an IPS patch that turns the verified base ROM `base` into this
+    /// game.
+    pub fn ips_patch(&self, base: &[u8]) -> Result<Vec<u8>, Error> {
+        let base = verify_rom(base.to_vec())?;
+        neutopia::ips::create_patch(&base, &self.data)
+    }
+
+    /// Returns the game in `format`.  `base` is the ROM the game was
+    /// randomized from.
+    pub fn output(&self, base: &[u8], format: OutputFormat) -> Result<Vec<u8>, Error> {
+        match format {
+            OutputFormat::Rom => Ok(self.data.clone()),
+            OutputFormat::Ips => self.ips_patch(base),
+            OutputFormat::Bps => self.bps_patch(base),
+        }
+    }
+}
+
 // Shuffle all items within each crypt.  Does not touch overworld items.
-fn crypt_rando(rng: &mut impl Rng, rom_data: &[u8]) -> Result<Vec<u8>, Error> {
+fn crypt_rando(rng: &mut impl Rng, rom_data: &[u8], ledger: &mut Ledger) -> Result<Vec<u8>, Error> {
     let mut n = Neutopia::new(rom_data)?;
 
     for area_idx in 0x4..=0xb {
@@ -55,8 +150,8 @@ fn crypt_rando(rng: &mut impl Rng, rom_data: &[u8]) -> Result<Vec<u8>, Error> {
             // Chest is in current area
             (chest.area == area_idx)
                 // Chest does not contain medallion
-                && (chest.info.item_id < 0x12 || chest.info.item_id >= (0x12 + 8))
-        });
+                && !chest.info.item().is_medallion()
+        })?;
 
         // Shuffle the chests.
         let mut randomized_chests: Vec<rom::Chest> =
@@ -71,17 +166,36 @@ fn crypt_rando(rng: &mut impl Rng, rom_data: &[u8]) -> Result<Vec<u8>, Error> {
         n.update_chests(&chests)?;
     }
 
-    n.write()
+    n.write_tracked(ledger)
+}
+
+// Choose one of the `checks` that are open for `item`.
+fn choose_check<'a>(
+    rng: &mut impl Rng,
+    checks: &'a [Check],
+    item: &state::Item,
+) -> Result<&'a Check, Error> {
+    checks.choose(rng).ok_or_else(|| {
+        format_err!(
+            "no reachable check is left for {}",
+            item.info.get_item_name()
+        )
+    })
 }
 
 // Shuffle all items across crypts and overworld.  Does not contain logic
 // to make sure seed is completable.
-fn global_rando(rng: &mut impl Rng, rom_data: &[u8]) -> Result<Vec<u8>, Error> {
+fn global_rando(
+    rng: &mut impl Rng,
+    config: &Config,
+    rom_data: &[u8],
+    ledger: &mut Ledger,
+) -> Result<Vec<u8>, Error> {
     let n = Neutopia::new(rom_data)?;
 
-    let mut state = State::new(n)?;
-    let book = state.get_item_by_id(0xd)?;
-    let moss = state.get_item_by_id(0x5)?;
+    let mut state = State::new(n, config.equipment)?;
+    let book = state.get_item(rom::Item::BookOfRevival)?;
+    let moss = state.get_item(rom::Item::MoonbeamMoss)?;
 
     state.place_item(book, 0xc, 0x9, 0x0)?;
     state.place_item(moss, 0xc, 0x11, 0x1)?;
@@ -103,19 +217,13 @@ fn global_rando(rng: &mut impl Rng, rom_data: &[u8]) -> Result<Vec<u8>, Error> {
         }
     }
 
-    // Next place the fire wand, bell, shoes, and drop in logic
-    let mut items = state.filter_items(|item| {
-        item.info.item_id == 0x2
-            || item.info.item_id == 0x3
-            || item.info.item_id == 0xb
-            || item.info.item_id == 0xc
-    });
+    // Next place the fire wand, bell, shoes, drop, and swords in logic
+    let mut items = state.filter_items(|item| State::is_progression(&item.info));
     items.shuffle(rng);
-    while !items.is_empty() {
+    while let Some(item) = items.pop() {
         // Get all open checks and chose one
         let checks = state.filter_checks(|_| true);
-        let check = checks.choose(rng).unwrap();
-        let item = items.pop().unwrap();
+        let check = choose_check(rng, &checks, &item)?;
         state.place_item_by_loc(item, &check.loc())?;
     }
 
@@ -129,57 +237,125 @@ fn global_rando(rng: &mut impl Rng, rom_data: &[u8]) -> Result<Vec<u8>, Error> {
     while !state.is_complete() {
         // Get all open checks and chose one
         let checks = state.filter_checks(|_| true);
-        let check = checks.choose(rng).unwrap();
         let item = items.pop().unwrap();
+        let check = choose_check(rng, &checks, &item)?;
         state.place_item_by_loc(item, &check.loc())?;
     }
+
+    // No hint dialog slots are known yet so the default config places none.
+    let hints = hints::generate_hints(rng, &HintConfig::default(), state.placements());
+
     let n = state.finalize()?;
-    n.write()
+    let mut data = n.write_tracked(ledger)?;
+    hints::write_hints(&mut data, &hints, ledger)?;
+
+    Ok(data)
 }
 
 fn verify_rom(data: Vec<u8>) -> Result<Vec<u8>, Error> {
-    // Verify
-    let info = neutopia::verify(&data)?;
-    if !info.known {
-        return Err(format_err!(
-            "Rom with MD5 hash {} is unrecognized.",
-            &info.md5_hash
-        ));
-    }
-    if info.region != Region::NA {
-        return Err(format_err!(
-            "Region {:?} rom not supported.  Please use NA rom.",
-            &info.region
-        ));
-    }
-
+    let info = neutopia::verify_supported(&data)?;
     if info.headered {
-        Ok(data[0x200..].to_vec())
+        Ok(data[bank::HEADER_LEN..].to_vec())
     } else {
         Ok(data)
     }
 }
 
 fn apply_patch<W: Write + Seek>(w: &mut W, patch_data: &[u8]) -> Result<(), Error> {
-    let patch = Patch::parse(patch_data)?;
+    let (hunks, _) = neutopia::ips::read_ips(patch_data)?;
 
-    for hunk in patch.hunks() {
-        w.seek(SeekFrom::Start(hunk.offset() as u64))?;
-        w.write_all(hunk.payload())?;
+    for hunk in hunks {
+        w.seek(SeekFrom::Start(hunk.offset as u64))?;
+        w.write_all(&hunk.data)?;
     }
 
     Ok(())
 }
 
-fn apply_patches(data: &mut [u8]) -> Result<(), Error> {
-    let mut c = Cursor::new(data);
+fn select_patches(config: &Config) -> Result<Vec<&'static patches::PatchInfo>, Error> {
+    let mut enabled = config.patches.clone();
     for patch in patches::PATCHES.iter() {
-        apply_patch(&mut c, patch)?;
+        if !patch.optional && enabled.contains(patch.name) {
+            return Err(format_err!(
+                "patch {} can't be selected directly.",
+                patch.name
+            ));
+        }
     }
-    Ok(())
+
+    enabled.insert(patches::RANDO_INFO.to_string());
+    match config.equipment {
+        EquipmentMode::Vanilla => (),
+        EquipmentMode::NoDowngrade => {
+            enabled.insert(patches::NO_DOWNGRADE.to_string());
+        }
+        EquipmentMode::Progressive => {
+            enabled.insert(patches::PROGRESSIVE_ITEMS.to_string());
+        }
+    }
+
+    patches::resolve(&patches::PATCHES, &enabled)
+}
+
+fn apply_patches(
+    config: &Config,
+    data: &mut [u8],
+) -> Result<Vec<&'static patches::PatchInfo>, Error> {
+    let selected = select_patches(config)?;
+
+    let conflicts = patches::find_conflicts(&selected)?;
+    if !conflicts.is_empty() && !config.allow_patch_conflicts {
+        let conflicts: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+        return Err(format_err!(
+            "Patch conflicts found: {}",
+            conflicts.join("; ")
+        ));
+    }
+
+    let mut c = Cursor::new(data);
+    for patch in &selected {
+        apply_patch(&mut c, patch.data)?;
+    }
+
+    Ok(selected)
+}
+
+// Claim the ranges modified by `applied` patches in a new ledger.
+//
+// Patch hunks that fall entirely within the game data parsed by
+// `NeutopiaRom` are carried through the data model and rewritten along with
+// it so they are not claimed.
+fn patch_ledger(
+    config: &Config,
+    applied: &[&patches::PatchInfo],
+    data: &[u8],
+) -> Result<Ledger, Error> {
+    let data_intervals = NeutopiaRom::new(data)?.data_intervals();
+
+    let mut ledger = Ledger::new();
+    for patch in applied {
+        let owner = format!("patch {}", patch.name);
+        for interval in patches::patch_intervals(patch)?.get_intervals() {
+            if data_intervals.covers(interval.start, interval.end) {
+                continue;
+            }
+            if config.allow_patch_conflicts {
+                ledger.record(&owner, interval.start, interval.end);
+            } else {
+                ledger.claim(&owner, interval.start, interval.end)?;
+            }
+        }
+    }
+
+    Ok(ledger)
 }
 
 pub fn randomize(config: &Config, data: &[u8]) -> Result<RandomizedGame, Error> {
+    randomize_rom(config, verify_rom(data.to_vec())?)
+}
+
+// Randomize an un-headered ROM that has already been verified.
+fn randomize_rom(config: &Config, mut buffer: Vec<u8>) -> Result<RandomizedGame, Error> {
     // Let the user specify a seed in base36.  Otherwise randomly generate one.
     let seed = match &config.seed {
         Some(s) => u64::from_str_radix(s, 36)
@@ -189,18 +365,157 @@ pub fn randomize(config: &Config, data: &[u8]) -> Result<RandomizedGame, Error>
 
     let mut rng = Pcg32::seed_from_u64(seed);
 
-    let mut buffer = verify_rom(data.to_vec())?;
+    let applied = apply_patches(config, &mut buffer)?;
 
-    apply_patches(&mut buffer)?;
+    let mut ledger = patch_ledger(config, &applied, &buffer)?;
 
-    let new_data = match config.ty {
-        RandoType::Local => crypt_rando(&mut rng, &buffer)?,
-        RandoType::Global => global_rando(&mut rng, &buffer)?,
+    let mut new_data = match config.ty {
+        RandoType::Local => crypt_rando(&mut rng, &buffer, &mut ledger)?,
+        RandoType::Global => global_rando(&mut rng, config, &buffer, &mut ledger)?,
         _ => buffer,
     };
 
+    patches::write_applied(&mut new_data, &applied, &mut ledger)?;
+
     Ok(RandomizedGame {
         seed: format!("{:#}", radix_36(seed)),
         data: new_data,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use neutopia::rom::object::{ObjectInfo, TableEntry};
+    use neutopia::synthetic::{RomBuilder, RoomSpec};
+
+    use super::*;
+
+    fn chest(item: rom::Item) -> rom::Chest {
+        let mut chest = rom::Chest {
+            item_id: 0,
+            arg: 0,
+            text: 0x85,
+            unknown: 0x41,
+        };
+        chest.set_item(item).unwrap();
+        chest
+    }
+
+    // Each crypt has chests with bombs, a crystal ball, a crypt key, and its
+    // medallion in its first rooms.
+    fn synthetic_rom() -> Vec<u8> {
+        let mut builder = RomBuilder::new();
+        for area in 0x4..=0xb {
+            let items = [
+                rom::Item::Bombs(area as u8),
+                rom::Item::CrystalBall,
+                rom::Item::CryptKey,
+                rom::Item::Medallion(area as u8 - 3),
+            ];
+            for (i, item) in items.iter().enumerate() {
+                let object = TableEntry::Object(ObjectInfo {
+                    x: 3,
+                    y: 4,
+                    id: 0x4c + i as u8,
+                });
+                builder = builder
+                    .room(
+                        area,
+                        i,
+                        RoomSpec {
+                            objects: vec![object],
+                            ..Default::default()
+                        },
+                    )
+                    .chest(area, i, chest(*item));
+            }
+        }
+        builder.build().unwrap()
+    }
+
+    fn config(ty: RandoType, patches: &[&str]) -> Config {
+        Config {
+            ty,
+            seed: Some("1".to_string()),
+            equipment: EquipmentMode::NoDowngrade,
+            patches: patches.iter().map(|p| p.to_string()).collect(),
+            allow_patch_conflicts: false,
+        }
+    }
+
+    fn items(n: &Neutopia, area: u8) -> Vec<rom::Item> {
+        let mut items: Vec<rom::Item> = n
+            .filter_chests(|c| c.area == area)
+            .unwrap()
+            .iter()
+            .map(|c| c.info.item())
+            .collect();
+        items.sort();
+        items
+    }
+
+    #[test]
+    fn test_local_randomize_synthetic() {
+        let data = synthetic_rom();
+        let config = config(RandoType::Local, &["intro-skip", "text-speedup"]);
+        let game = randomize_rom(&config, data.clone()).unwrap();
+
+        let original = Neutopia::new(&data).unwrap();
+        let randomized = Neutopia::new(&game.data).unwrap();
+        for area in 0x4..=0xb {
+            assert_eq!(items(&original, area), items(&randomized, area));
+            // Medallions stay put.
+            let medallion = randomized
+                .filter_chests(|c| c.area == area && c.info.item().is_medallion())
+                .unwrap();
+            assert_eq!(medallion[0].room, 3);
+        }
+
+        assert_eq!(
+            patches::read_applied(&game.data).unwrap(),
+            vec!["intro-skip", "text-speedup", "no-downgrade", "rando-info"]
+        );
+        assert_eq!(neutopia::roundtrip::round_trip(&game.data).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_global_randomize_synthetic() {
+        let data = state::tests::checks_rom();
+        for mode in &[EquipmentMode::NoDowngrade, EquipmentMode::Progressive] {
+            for seed in 0..20 {
+                let config = Config {
+                    seed: Some(seed.to_string()),
+                    equipment: *mode,
+                    ..config(RandoType::Global, &[])
+                };
+                let game = randomize_rom(&config, data.clone()).unwrap();
+                let n = Neutopia::new(&game.data).unwrap();
+                assert_eq!(n.filter_chests(|c| c.area == 0xb).unwrap().len(), 6);
+            }
+        }
+
+        // Any sword can be replaced by a lower tier in vanilla mode so
+        // Crypt 8 is never in logic.
+        let config = Config {
+            equipment: EquipmentMode::Vanilla,
+            ..config(RandoType::Global, &[])
+        };
+        let err = randomize_rom(&config, data).err().unwrap();
+        assert!(
+            err.to_string()
+                .starts_with("no reachable check is left for"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_randomize_is_deterministic() {
+        let data = synthetic_rom();
+        let config = config(RandoType::Local, &[]);
+        let a = randomize_rom(&config, data.clone()).unwrap();
+        let b = randomize_rom(&config, data).unwrap();
+        assert_eq!(a.seed, "1");
+        assert!(a.data == b.data);
+    }
+}
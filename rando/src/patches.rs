@@ -1,11 +1,494 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use failure::{format_err, Error};
 use lazy_static::lazy_static;
+use neutopia::{
+    interval::{Interval, IntervalStore},
+    ips, rommap, Ledger,
+};
+
+/// Label addresses and file offsets from the assembled patches, one module
+/// per patch (i.e. `symbols::no_downgrade::HANDLE_UPGRADEABLE_ITEM`).
+pub mod symbols {
+    include!(concat!(env!("OUT_DIR"), "/asm/symbols.rs"));
+}
+
+/// An assembled patch along with the metadata needed to select it.
+#[derive(Debug)]
+pub struct PatchInfo {
+    /// Short name used to select the patch.
+    pub name: &'static str,
+
+    /// One line, human readable description of the patch.
+    pub description: &'static str,
+
+    /// Whether the patch is applied when the user does not choose otherwise.
+    pub default: bool,
+
+    /// Whether the user may toggle the patch.  Patches that are not optional
+    /// are selected by other settings (i.e. [`crate::EquipmentMode`]).
+    pub optional: bool,
+
+    /// Names of patches that must also be applied.
+    pub requires: &'static [&'static str],
+
+    /// Names of patches that may not be applied alongside this one.
+    pub conflicts: &'static [&'static str],
+
+    /// IPS patch data.
+    pub data: &'static [u8],
+
+    /// Labels defined by the patch source as `(name, address, offset)`.
+    pub symbols: &'static [(&'static str, u32, usize)],
+
+    /// Data slots declared by the patch source as `(name, offset, size)`.
+    /// See [`fill_slot`].
+    pub slots: &'static [(&'static str, usize, usize)],
+}
+
+pub const NO_DOWNGRADE: &str = "no-downgrade";
+pub const PROGRESSIVE_ITEMS: &str = "progressive-items";
+pub const RANDO_INFO: &str = "rando-info";
 
 lazy_static! {
-    pub static ref PATCHES: Vec<&'static [u8]> = vec![
-        include_bytes!(concat!(env!("OUT_DIR"), "/asm/expand-save-state.ips")),
-        include_bytes!(concat!(env!("OUT_DIR"), "/asm/intro-skip.ips")),
-        include_bytes!(concat!(env!("OUT_DIR"), "/asm/no-downgrade.ips")),
-        include_bytes!(concat!(env!("OUT_DIR"), "/asm/open-stairs.ips")),
-        include_bytes!(concat!(env!("OUT_DIR"), "/asm/text-speedup.ips")),
+    pub static ref PATCHES: Vec<PatchInfo> = vec![
+        PatchInfo {
+            name: "expand-save-state",
+            description: "Extend the password to save all 24 bytes of game state.",
+            default: true,
+            optional: true,
+            requires: &[],
+            conflicts: &[],
+            data: include_bytes!(concat!(env!("OUT_DIR"), "/asm/expand-save-state.ips")),
+            symbols: symbols::expand_save_state::SYMBOLS,
+            slots: symbols::expand_save_state::SLOTS,
+        },
+        PatchInfo {
+            name: "intro-skip",
+            description: "Skip the opening cut scene.",
+            default: true,
+            optional: true,
+            requires: &[],
+            conflicts: &[],
+            data: include_bytes!(concat!(env!("OUT_DIR"), "/asm/intro-skip.ips")),
+            symbols: symbols::intro_skip::SYMBOLS,
+            slots: symbols::intro_skip::SLOTS,
+        },
+        PatchInfo {
+            name: "open-stairs",
+            description: "Open the stairs that are normally revealed by story events.",
+            default: true,
+            optional: true,
+            requires: &[],
+            conflicts: &[],
+            data: include_bytes!(concat!(env!("OUT_DIR"), "/asm/open-stairs.ips")),
+            symbols: symbols::open_stairs::SYMBOLS,
+            slots: symbols::open_stairs::SLOTS,
+        },
+        PatchInfo {
+            name: "text-speedup",
+            description: "Print dialog text faster.",
+            default: true,
+            optional: true,
+            requires: &[],
+            conflicts: &[],
+            data: include_bytes!(concat!(env!("OUT_DIR"), "/asm/text-speedup.ips")),
+            symbols: symbols::text_speedup::SYMBOLS,
+            slots: symbols::text_speedup::SLOTS,
+        },
+        // The equipment patches both replace the sword/armor/shield handler
+        // so only one of them may be applied.
+        PatchInfo {
+            name: NO_DOWNGRADE,
+            description: "Never replace equipment with a lower tier item.",
+            default: false,
+            optional: false,
+            requires: &[],
+            conflicts: &[PROGRESSIVE_ITEMS],
+            data: include_bytes!(concat!(env!("OUT_DIR"), "/asm/no-downgrade.ips")),
+            symbols: symbols::no_downgrade::SYMBOLS,
+            slots: symbols::no_downgrade::SLOTS,
+        },
+        PatchInfo {
+            name: PROGRESSIVE_ITEMS,
+            description: "Upgrade equipment by a tier with each pickup.",
+            default: false,
+            optional: false,
+            requires: &[],
+            conflicts: &[NO_DOWNGRADE],
+            data: include_bytes!(concat!(env!("OUT_DIR"), "/asm/progressive-items.ips")),
+            symbols: symbols::progressive_items::SYMBOLS,
+            slots: symbols::progressive_items::SLOTS,
+        },
+        // Always applied so there is a slot to record the other patches in.
+        PatchInfo {
+            name: RANDO_INFO,
+            description: "Reserve space to record how the ROM was randomized.",
+            default: true,
+            optional: false,
+            requires: &[],
+            conflicts: &[],
+            data: include_bytes!(concat!(env!("OUT_DIR"), "/asm/rando-info.ips")),
+            symbols: symbols::rando_info::SYMBOLS,
+            slots: symbols::rando_info::SLOTS,
+        },
     ];
 }
+
+/// Returns the names of the optional patches that are enabled by default.
+pub fn default_patches() -> BTreeSet<String> {
+    PATCHES
+        .iter()
+        .filter(|p| p.optional && p.default)
+        .map(|p| p.name.to_string())
+        .collect()
+}
+
+/// Look up the patches in `enabled` and check their dependencies and
+/// conflicts.
+///
+/// Patches are returned in `registry` order.
+pub fn resolve<'a>(
+    registry: &'a [PatchInfo],
+    enabled: &BTreeSet<String>,
+) -> Result<Vec<&'a PatchInfo>, Error> {
+    for name in enabled {
+        if !registry.iter().any(|p| p.name == name) {
+            return Err(format_err!("unknown patch {}", name));
+        }
+    }
+
+    let patches: Vec<&PatchInfo> = registry
+        .iter()
+        .filter(|p| enabled.contains(p.name))
+        .collect();
+
+    for patch in &patches {
+        for dep in patch.requires {
+            if !enabled.contains(*dep) {
+                return Err(format_err!("patch {} requires patch {}", patch.name, dep));
+            }
+        }
+        for conflict in patch.conflicts {
+            if enabled.contains(*conflict) {
+                return Err(format_err!(
+                    "patch {} conflicts with patch {}",
+                    patch.name,
+                    conflict
+                ));
+            }
+        }
+    }
+
+    Ok(patches)
+}
+
+/// ROM ranges written by more than one patch.
+#[derive(Debug)]
+pub struct PatchConflict {
+    pub patches: (&'static str, &'static str),
+    pub ranges: Vec<Interval<usize>>,
+}
+
+impl fmt::Display for PatchConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "patches {} and {} both modify",
+            self.patches.0, self.patches.1
+        )?;
+        for (i, range) in self.ranges.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(f, "{} {:05x}-{:05x}", sep, range.start, range.end)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the ROM ranges written by `patch`.
+pub fn patch_intervals(patch: &PatchInfo) -> Result<IntervalStore<usize>, Error> {
+    let (hunks, _) = ips::read_ips(patch.data)
+        .map_err(|e| format_err!("can't parse patch {}: {}", patch.name, e))?;
+
+    let mut intervals = IntervalStore::new();
+    for hunk in hunks {
+        let offset = hunk.offset as usize;
+        intervals.add(offset, offset + hunk.data.len());
+    }
+
+    Ok(intervals)
+}
+
+/// Find every pair of `patches` that write to the same bytes.
+pub fn find_conflicts(patches: &[&PatchInfo]) -> Result<Vec<PatchConflict>, Error> {
+    let intervals = patches
+        .iter()
+        .map(|p| patch_intervals(p))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let mut conflicts = Vec::new();
+    for (i, a) in intervals.iter().enumerate() {
+        for (j, b) in intervals.iter().enumerate().skip(i + 1) {
+            let ranges = a.intersection(b);
+            if !ranges.is_empty() {
+                conflicts.push(PatchConflict {
+                    patches: (patches[i].name, patches[j].name),
+                    ranges,
+                });
+            }
+        }
+    }
+
+    Ok(conflicts)
+}
+
+/// Write `value` into the data slot `name` declared by `patch`.
+///
+/// The slot must be exactly `value.len()` bytes and lie within the bytes
+/// written by `patch`, which must already be applied to `data`.
+pub fn fill_slot(
+    data: &mut [u8],
+    patch: &PatchInfo,
+    name: &str,
+    value: &[u8],
+    ledger: &mut Ledger,
+) -> Result<(), Error> {
+    let (_, offset, size) = patch
+        .slots
+        .iter()
+        .find(|(slot, _, _)| *slot == name)
+        .ok_or_else(|| format_err!("patch {} has no slot {}", patch.name, name))?;
+    let (offset, size) = (*offset, *size);
+
+    if value.len() != size {
+        return Err(format_err!(
+            "slot {} in patch {} is {} bytes, got {}",
+            name,
+            patch.name,
+            size,
+            value.len()
+        ));
+    }
+    if offset + size > data.len() || !patch_intervals(patch)?.covers(offset, offset + size) {
+        return Err(format_err!(
+            "slot {} ({:05x}-{:05x}) is not part of patch {}",
+            name,
+            offset,
+            offset + size,
+            patch.name
+        ));
+    }
+
+    ledger.claim(&format!("patch {}", patch.name), offset, offset + size)?;
+    data[offset..offset + size].copy_from_slice(value);
+
+    Ok(())
+}
+
+const APPLIED_MAGIC: &[u8] = b"NRND";
+
+/// Record the names of `patches` in the rando info slot.
+///
+/// The [`RANDO_INFO`] patch must be one of `patches`.
+pub fn write_applied(
+    data: &mut [u8],
+    patches: &[&PatchInfo],
+    ledger: &mut Ledger,
+) -> Result<(), Error> {
+    let info_patch = patches
+        .iter()
+        .find(|p| p.name == RANDO_INFO)
+        .ok_or_else(|| format_err!("patch {} isn't applied", RANDO_INFO))?;
+
+    let names: Vec<&str> = patches.iter().map(|p| p.name).collect();
+    let names = names.join(",");
+
+    let mut info = APPLIED_MAGIC.to_vec();
+    info.extend_from_slice(names.as_bytes());
+    info.push(0);
+
+    let size = symbols::rando_info::RANDO_INFO_SIZE;
+    if info.len() > size {
+        return Err(format_err!(
+            "applied patch list is {} bytes, more than the {} available",
+            info.len(),
+            size
+        ));
+    }
+    info.resize(size, 0);
+
+    fill_slot(data, info_patch, "rando_info", &info, ledger)
+}
+
+/// Read back the patch names recorded by [`write_applied`].
+///
+/// Returns `None` if the ROM does not have a rando info block.
+pub fn read_applied(data: &[u8]) -> Option<Vec<String>> {
+    let info = data.get(rommap::RANDO_INFO..rommap::RANDO_INFO + rommap::RANDO_INFO_LEN)?;
+    if !info.starts_with(APPLIED_MAGIC) {
+        return None;
+    }
+
+    let names = &info[APPLIED_MAGIC.len()..];
+    let len = names.iter().position(|b| *b == 0)?;
+    let names = std::str::from_utf8(&names[..len]).ok()?;
+
+    Some(
+        names
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(|name| name.to_string())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(
+        name: &'static str,
+        requires: &'static [&'static str],
+        conflicts: &'static [&'static str],
+    ) -> PatchInfo {
+        PatchInfo {
+            name,
+            description: "",
+            default: true,
+            optional: true,
+            requires,
+            conflicts,
+            data: &[],
+            symbols: &[],
+            slots: &[],
+        }
+    }
+
+    fn names(list: &[&str]) -> BTreeSet<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let registry = vec![
+            patch("a", &[], &[]),
+            patch("b", &["a"], &["c"]),
+            patch("c", &[], &[]),
+        ];
+
+        let resolved = resolve(&registry, &names(&["b", "a"])).unwrap();
+        let resolved: Vec<&str> = resolved.iter().map(|p| p.name).collect();
+        assert_eq!(resolved, vec!["a", "b"]);
+
+        assert!(resolve(&registry, &names(&["b"])).is_err());
+        assert!(resolve(&registry, &names(&["a", "b", "c"])).is_err());
+        assert!(resolve(&registry, &names(&["d"])).is_err());
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let a = PatchInfo {
+            data: b"PATCH\x00\x10\x00\x00\x04abcd\x00\x20\x00\x00\x01aEOF",
+            ..patch("a", &[], &[])
+        };
+        let b = PatchInfo {
+            data: b"PATCH\x00\x10\x02\x00\x04abcdEOF",
+            ..patch("b", &[], &[])
+        };
+        let c = PatchInfo {
+            data: b"PATCH\x00\x20\x01\x00\x01aEOF",
+            ..patch("c", &[], &[])
+        };
+
+        let conflicts = find_conflicts(&[&a, &b, &c]).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].patches, ("a", "b"));
+        assert_eq!(
+            conflicts[0].ranges,
+            vec![Interval {
+                start: 0x1002,
+                end: 0x1004
+            }]
+        );
+        assert_eq!(
+            conflicts[0].to_string(),
+            "patches a and b both modify 01002-01004"
+        );
+    }
+
+    #[test]
+    fn test_builtin_patches_do_not_conflict() {
+        for equipment in &[NO_DOWNGRADE, PROGRESSIVE_ITEMS] {
+            let mut enabled = default_patches();
+            enabled.insert(equipment.to_string());
+            let patches = resolve(&PATCHES, &enabled).unwrap();
+            let conflicts = find_conflicts(&patches).unwrap();
+            assert!(conflicts.is_empty(), "{:?}", conflicts);
+        }
+    }
+
+    #[test]
+    fn test_registry_is_consistent() {
+        for patch in PATCHES.iter() {
+            for name in patch.requires.iter().chain(patch.conflicts.iter()) {
+                assert!(PATCHES.iter().any(|p| p.name == *name), "{}", name);
+            }
+            let intervals = patch_intervals(patch).unwrap();
+            for (name, offset, size) in patch.slots {
+                assert!(intervals.covers(*offset, offset + size), "{}", name);
+            }
+        }
+        resolve(&PATCHES, &default_patches()).unwrap();
+    }
+
+    #[test]
+    fn test_fill_slot() {
+        let p = PatchInfo {
+            data: b"PATCH\x00\x10\x00\x00\x04\x00\x00\x00\x00EOF",
+            slots: &[("flags", 0x1002, 2), ("outside", 0x1004, 1)],
+            ..patch("p", &[], &[])
+        };
+        let mut data = vec![0u8; 0x2000];
+        let mut ledger = Ledger::new();
+
+        fill_slot(&mut data, &p, "flags", &[0x12, 0x34], &mut ledger).unwrap();
+        assert_eq!(&data[0x1000..0x1005], &[0, 0, 0x12, 0x34, 0]);
+
+        assert!(fill_slot(&mut data, &p, "flags", &[0x12], &mut ledger).is_err());
+        assert!(fill_slot(&mut data, &p, "missing", &[0], &mut ledger).is_err());
+        assert!(fill_slot(&mut data, &p, "outside", &[0], &mut ledger).is_err());
+
+        ledger.record("data", 0x1003, 0x1004);
+        assert!(fill_slot(&mut data, &p, "flags", &[0, 0], &mut ledger).is_err());
+    }
+
+    #[test]
+    fn test_symbols() {
+        assert_eq!(symbols::rando_info::RANDO_INFO_OFFSET, rommap::RANDO_INFO);
+        assert_eq!(symbols::rando_info::RANDO_INFO_SIZE, rommap::RANDO_INFO_LEN);
+        assert_eq!(symbols::no_downgrade::GET_SWORD_HANDLER, 0xddf1);
+        assert_eq!(symbols::no_downgrade::GET_SWORD_HANDLER_OFFSET, 0x7df1);
+        assert_eq!(
+            symbols::progressive_items::HANDLE_PROGRESSIVE_ITEM_OFFSET,
+            symbols::no_downgrade::HANDLE_UPGRADEABLE_ITEM_OFFSET
+        );
+    }
+
+    #[test]
+    fn test_applied_round_trip() {
+        let mut data = vec![0u8; 0x60000];
+        assert_eq!(read_applied(&data), None);
+
+        let patches: Vec<&PatchInfo> = PATCHES.iter().collect();
+        write_applied(&mut data, &patches, &mut Ledger::new()).unwrap();
+        let applied = read_applied(&data).unwrap();
+        let expected: Vec<String> = PATCHES.iter().map(|p| p.name.to_string()).collect();
+        assert_eq!(applied, expected);
+
+        let without_info: Vec<&PatchInfo> =
+            PATCHES.iter().filter(|p| p.name != RANDO_INFO).collect();
+        let err = write_applied(&mut data, &without_info, &mut Ledger::new()).unwrap_err();
+        assert_eq!(err.to_string(), "patch rando-info isn't applied");
+    }
+}
@@ -4,6 +4,8 @@ use failure::{format_err, Error};
 use neutopia::{self, rom, Neutopia};
 use serde::{Deserialize, Serialize};
 
+use crate::EquipmentMode;
+
 static CHECKS_DATA: &[u8] = include_bytes!("checks.json");
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -13,6 +15,29 @@ pub enum Gate {
     FalconShoes,
     FireWand,
     Bell,
+    // Requires a number of sword pickups, i.e. 2 for the steel sword.
+    // Outside of progressive mode, the `n`th lowest tier in the item pool
+    // satisfies a gate of `n`.
+    Swords(u8),
+}
+
+// Returns the number of sword pickups the player is guaranteed to have given
+// the tiers in the item pool and the tiers placed so far.
+//
+// In vanilla mode picking up a lower tier replaces the current sword, and
+// any sword in the pool may be picked up last, so only the lowest tier is
+// kept for sure.
+fn sword_level(pool: &[u8], placed: &[u8], equipment: EquipmentMode) -> usize {
+    let kept = match equipment {
+        EquipmentMode::Progressive => return placed.len(),
+        EquipmentMode::NoDowngrade => placed.iter().max(),
+        EquipmentMode::Vanilla if placed.is_empty() => None,
+        EquipmentMode::Vanilla => pool.iter().min(),
+    };
+    match kept {
+        Some(kept) => pool.iter().filter(|tier| *tier <= kept).count(),
+        None => 0,
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -35,6 +60,13 @@ impl Check {
     }
 }
 
+/// Record of an item placed at a check.
+#[derive(Clone, Debug)]
+pub struct Placement {
+    pub check: Check,
+    pub item: rom::Chest,
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) struct LocationId {
     pub area: u8,
@@ -55,36 +87,60 @@ pub(crate) struct State {
     unplaced_items: BTreeSet<Item>,
     cleared_gates: BTreeSet<Gate>,
 
+    equipment: EquipmentMode,
+    sword_pool: Vec<u8>,
+    placed_swords: Vec<u8>,
+
     assigned_chests: Vec<neutopia::Chest>,
+    placements: Vec<Placement>,
 
     n: Neutopia,
 }
 
 impl State {
-    pub fn new(n: Neutopia) -> Result<Self, Error> {
+    pub fn new(n: Neutopia, equipment: EquipmentMode) -> Result<Self, Error> {
         let mut unplaced_items = BTreeSet::new();
-
-        // Filter out end game area and medallions
-        let chests = n.filter_chests(|chest| (chest.area < 0x10) && (chest.info.item_id < 0x12));
+        let mut sword_pool = Vec::new();
+
+        // Filter out end game area, medallions, and the unknown ids past
+        // them (i.e. the 0x20 placeholder)
+        let chests = n.filter_chests(|chest| {
+            chest.area < 0x10
+                && !matches!(
+                    chest.info.item(),
+                    rom::Item::Medallion(_) | rom::Item::Unknown(rom::Item::MEDALLION_ID..=0xff, _)
+                )
+        })?;
 
         for chest in chests {
             // Lock crystal balls and crypt keys to their area
-            let area_lock = match chest.info.item_id {
-                0x10 | 0x11 => Some(chest.area),
-                _ => None,
+            let area_lock = if chest.info.item().is_area_locked() {
+                Some(chest.area)
+            } else {
+                None
             };
 
+            if let rom::Item::Sword(tier) = chest.info.item() {
+                sword_pool.push(tier);
+            }
+
             unplaced_items.insert(Item {
                 info: chest.info,
                 area_lock,
             });
         }
 
+        sword_pool.sort_unstable();
+
         Ok(Self {
             unassigned_checks: get_checks()?,
             unplaced_items,
             cleared_gates: BTreeSet::new(),
+            equipment,
+            sword_pool,
+            placed_swords: Vec::new(),
             assigned_chests: Vec::new(),
+            placements: Vec::new(),
             n,
         })
     }
@@ -94,16 +150,30 @@ impl State {
         self.unassigned_checks.is_empty()
     }
 
-    fn gate_for_item(item: &Item) -> Option<Gate> {
-        match item.info.item_id {
-            0x02 => Some(Gate::FireWand),
-            0x03 => Some(Gate::Bell),
-            0x0b => Some(Gate::FalconShoes),
-            0x0c => Some(Gate::RainbowDrop),
+    pub fn gate_for_item(item: &rom::Chest) -> Option<Gate> {
+        match item.item() {
+            rom::Item::FireWand => Some(Gate::FireWand),
+            rom::Item::SkyBell => Some(Gate::Bell),
+            rom::Item::FalconShoes => Some(Gate::FalconShoes),
+            rom::Item::RainbowDrop => Some(Gate::RainbowDrop),
             _ => None,
         }
     }
 
+    /// Returns true for items that can open gated checks.
+    pub fn is_progression(item: &rom::Chest) -> bool {
+        Self::gate_for_item(item).is_some() || matches!(item.item(), rom::Item::Sword(_))
+    }
+
+    fn is_gate_cleared(&self, gate: &Gate) -> bool {
+        match gate {
+            Gate::Swords(n) => {
+                sword_level(&self.sword_pool, &self.placed_swords, self.equipment) >= *n as usize
+            }
+            _ => self.cleared_gates.contains(gate),
+        }
+    }
+
     pub fn place_item(&mut self, item: Item, area: u8, room: u8, index: u8) -> Result<(), Error> {
         self.place_item_by_loc(item, &LocationId { area, room, index })
     }
@@ -127,10 +197,19 @@ impl State {
             return Err(format_err!("can't place unknown item {:?}", item));
         }
 
-        if let Some(gate) = Self::gate_for_item(&item) {
+        if let Some(gate) = Self::gate_for_item(&item.info) {
             self.cleared_gates.insert(gate);
         }
 
+        if let rom::Item::Sword(tier) = item.info.item() {
+            self.placed_swords.push(tier);
+        }
+
+        self.placements.push(Placement {
+            check: check.clone(),
+            item: item.info.clone(),
+        });
+
         let chest = neutopia::Chest {
             info: item.info,
             area: check.area,
@@ -156,12 +235,12 @@ impl State {
         items
     }
 
-    pub fn get_item_by_id(&self, id: u8) -> Result<Item, Error> {
-        let items = self.filter_items(|item| item.info.item_id == id);
+    pub fn get_item(&self, item: rom::Item) -> Result<Item, Error> {
+        let items = self.filter_items(|i| i.info.item() == item);
         if items.len() > 1 {
-            Err(format_err!("Found {} items with id {:02}", items.len(), id))
+            Err(format_err!("Found {} items matching {}", items.len(), item))
         } else if items.is_empty() {
-            Err(format_err!("Found no items with id {:02}", id))
+            Err(format_err!("Found no items matching {}", item))
         } else {
             Ok(items[0].clone())
         }
@@ -172,7 +251,7 @@ impl State {
         'check: for check in self.unassigned_checks.values() {
             // Filter out gated checks first.
             for gate in &check.gates {
-                if !self.cleared_gates.contains(gate) {
+                if !self.is_gate_cleared(gate) {
                     continue 'check;
                 }
             }
@@ -195,6 +274,10 @@ impl State {
         checks
     }
 
+    pub fn placements(&self) -> &[Placement] {
+        &self.placements
+    }
+
     pub fn finalize(mut self) -> Result<Neutopia, Error> {
         self.n.update_chests(&self.assigned_chests)?;
         Ok(self.n)
@@ -202,7 +285,7 @@ impl State {
 }
 
 fn get_checks() -> Result<BTreeMap<LocationId, Check>, Error> {
-    let checks_vec: Vec<Check> = serde_json::from_slice(&CHECKS_DATA)
+    let checks_vec: Vec<Check> = serde_json::from_slice(CHECKS_DATA)
         .map_err(|e| format_err!("failed to parse checks JSON: {}", e))?;
 
     let mut checks = BTreeMap::new();
@@ -224,3 +307,164 @@ fn get_checks() -> Result<BTreeMap<LocationId, Check>, Error> {
 
     Ok(checks)
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Returns the item a check holds in the vanilla game, going by its name.
+    fn vanilla_item(check: &Check) -> rom::Item {
+        let name = check.name.rsplit(" - ").next().unwrap();
+        (0..rom::Item::MEDALLION_ID)
+            .flat_map(|id| (1..=20).map(move |arg| rom::Item::from_ids(id, arg)))
+            .find(|item| item.to_string() == name)
+            .unwrap_or_else(|| panic!("no item named {}", name))
+    }
+
+    /// Build a ROM with a chest for every check in `checks.json` holding its
+    /// vanilla item.  The item pool is a set so each chest gets its own
+    /// `unknown` byte to keep duplicate items apart.
+    pub(crate) fn checks_rom() -> Vec<u8> {
+        let mut rooms: BTreeMap<(u8, u8), Vec<rom::object::TableEntry>> = BTreeMap::new();
+        let mut chests: BTreeMap<u8, usize> = BTreeMap::new();
+        let mut builder = neutopia::synthetic::RomBuilder::new();
+        for (i, check) in get_checks().unwrap().values().enumerate() {
+            let slot = chests.entry(check.area).or_default();
+            let mut info = chest(0);
+            info.unknown = i as u8;
+            info.set_item(vanilla_item(check)).unwrap();
+            builder = builder.chest(check.area as usize, *slot, info);
+
+            let objects = rooms.entry((check.area, check.room)).or_default();
+            objects.push(rom::object::TableEntry::Object(rom::ObjectInfo {
+                x: objects.len() as u8,
+                y: 1,
+                id: neutopia::FIRST_CHEST_OBJECT + *slot as u8,
+            }));
+            *slot += 1;
+        }
+        for ((area, room), objects) in rooms {
+            builder = builder.room(
+                area as usize,
+                room as usize,
+                neutopia::synthetic::RoomSpec {
+                    objects,
+                    ..Default::default()
+                },
+            );
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_sword_level() {
+        let pool = [2, 3, 4];
+        let no_downgrade = EquipmentMode::NoDowngrade;
+        assert_eq!(sword_level(&pool, &[], no_downgrade), 0);
+        assert_eq!(sword_level(&pool, &[3], no_downgrade), 2);
+        assert_eq!(sword_level(&pool, &[4, 2], no_downgrade), 3);
+
+        let progressive = EquipmentMode::Progressive;
+        assert_eq!(sword_level(&pool, &[], progressive), 0);
+        assert_eq!(sword_level(&pool, &[4], progressive), 1);
+        assert_eq!(sword_level(&pool, &[4, 2], progressive), 2);
+
+        let vanilla = EquipmentMode::Vanilla;
+        assert_eq!(sword_level(&pool, &[], vanilla), 0);
+        assert_eq!(sword_level(&pool, &[4], vanilla), 1);
+        assert_eq!(sword_level(&pool, &[4, 3, 2], vanilla), 1);
+    }
+
+    #[test]
+    fn test_sword_gates() {
+        let crypt_8 = |state: &State| state.filter_checks(|c| c.area == 0xb).len();
+        for equipment in &[EquipmentMode::NoDowngrade, EquipmentMode::Progressive] {
+            let n = Neutopia::new(&checks_rom()).unwrap();
+            let mut state = State::new(n, *equipment).unwrap();
+            let sword = |state: &State, tier| state.get_item(rom::Item::Sword(tier)).unwrap();
+
+            let drop = state.get_item(rom::Item::RainbowDrop).unwrap();
+            state.place_item(drop, 0x4, 0x12, 0).unwrap();
+            assert_eq!(crypt_8(&state), 0);
+
+            // Crypt 8 needs the steel sword, or two swords when progressive.
+            state.place_item(sword(&state, 2), 0x4, 0x1b, 0).unwrap();
+            assert_eq!(crypt_8(&state), 0);
+            state.place_item(sword(&state, 4), 0x5, 0x01, 0).unwrap();
+            assert_eq!(crypt_8(&state), 6, "{:?}", equipment);
+        }
+
+        let n = Neutopia::new(&checks_rom()).unwrap();
+        let mut state = State::new(n, EquipmentMode::NoDowngrade).unwrap();
+        let drop = state.get_item(rom::Item::RainbowDrop).unwrap();
+        state.place_item(drop, 0x4, 0x12, 0).unwrap();
+        let steel = state.get_item(rom::Item::Sword(3)).unwrap();
+        state.place_item(steel, 0x4, 0x1b, 0).unwrap();
+        assert_eq!(crypt_8(&state), 6);
+
+        // A lower tier can replace the steel sword so it doesn't count.
+        let n = Neutopia::new(&checks_rom()).unwrap();
+        let mut state = State::new(n, EquipmentMode::Vanilla).unwrap();
+        let drop = state.get_item(rom::Item::RainbowDrop).unwrap();
+        state.place_item(drop, 0x4, 0x12, 0).unwrap();
+        for (tier, room) in &[(3, 0x1b), (4, 0x01)] {
+            let sword = state.get_item(rom::Item::Sword(*tier)).unwrap();
+            state.place_item(sword, 0x4 + *tier - 3, *room, 0).unwrap();
+        }
+        assert_eq!(crypt_8(&state), 0);
+    }
+
+    pub(crate) fn chest(item_id: u8) -> rom::Chest {
+        rom::Chest {
+            item_id,
+            arg: 1,
+            text: 0x85,
+            unknown: 0x41,
+        }
+    }
+
+    #[test]
+    fn test_item_pool() {
+        let objects = (0..4)
+            .map(|i| {
+                rom::object::TableEntry::Object(rom::ObjectInfo {
+                    x: i,
+                    y: 1,
+                    id: neutopia::FIRST_CHEST_OBJECT + i,
+                })
+            })
+            .collect();
+        let data = neutopia::synthetic::RomBuilder::new()
+            .room(
+                4,
+                0,
+                neutopia::synthetic::RoomSpec {
+                    objects,
+                    ..Default::default()
+                },
+            )
+            .chest(4, 0, chest(0x07))
+            .chest(4, 1, chest(0x12))
+            .chest(4, 2, chest(0x1a))
+            .chest(4, 3, chest(0x20))
+            .build()
+            .unwrap();
+        let state = State::new(Neutopia::new(&data).unwrap(), EquipmentMode::NoDowngrade).unwrap();
+
+        let ids: Vec<u8> = state
+            .filter_items(|i| i.info.arg == 1)
+            .iter()
+            .map(|i| i.info.item_id)
+            .collect();
+        assert!(ids.contains(&0x07));
+        for id in &[0x12, 0x1a, 0x20] {
+            assert!(!ids.contains(id), "{:02x} in the item pool", id);
+        }
+    }
+
+    #[test]
+    fn test_parse_equipment_gate() {
+        let gates: Vec<Gate> = serde_json::from_str(r#"["bell", {"swords": 2}]"#).unwrap();
+        assert_eq!(gates, vec![Gate::Bell, Gate::Swords(2)]);
+    }
+}